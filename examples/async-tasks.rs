@@ -0,0 +1,130 @@
+//! Cooperative multitasking with `async fn`, using the executor and async drivers from the
+//! [temp-hal] example's HAL, on the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2)
+//! development kit.
+//!
+//! One task reads a [TCN75A](https://www.microchip.com/en-us/product/TCN75A) on pins 1.6 (SCK)
+//! and 1.7 (SDA) once a second and prints the temperature over the HW UART (9600 baud), as in
+//! the [temp-hal] example. Another toggles the red LED whenever the button on P1.3 is pressed
+//! (without debouncing). While both are waiting, the part sleeps in LPM0.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::asynch::{self, AsyncI2c, AsyncPort1, AsyncSerial, AsyncTimer};
+use hal::clock::{AclkDivider, ClockConfig};
+use hal::executor::{self, Task};
+use hal::gpio::{Edge, GpioExt, Output, PushPull, P1_0};
+use hal::lpm::LowPowerMode;
+use hal::tlv::DcoFrequency;
+use hal::{I2c, Serial, SfrIfg, Timer, WatchdogTimer};
+
+extern crate panic_msp430;
+
+use core::pin::pin;
+
+use embedded_hal::digital::blocking::ToggleableOutputPin;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+const TCN75A: u8 = 0x48;
+const TEMP_REG: u8 = 0x00;
+
+// "-128 C\n" at most.
+fn format_celsius(t: i8, buf: &mut [u8; 7]) -> &[u8] {
+    let mut i = buf.len() - 3;
+    buf[i..].copy_from_slice(b" C\n");
+
+    let mut n = i16::from(t).unsigned_abs();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+
+        if n == 0 {
+            break;
+        }
+    }
+
+    if t < 0 {
+        i -= 1;
+        buf[i] = b'-';
+    }
+
+    &buf[i..]
+}
+
+async fn report(mut timer: AsyncTimer, mut serial: AsyncSerial, mut i2c: AsyncI2c) {
+    let mut buf = [0; 7];
+
+    loop {
+        timer.wait().await;
+
+        // The upper byte of the temperature register is whole degrees.
+        let mut temp = [0; 2];
+        let line = match i2c.write_read(TCN75A, &[TEMP_REG], &mut temp).await {
+            Ok(()) => format_celsius(temp[0] as i8, &mut buf),
+            Err(_) => b"no sensor\n",
+        };
+
+        serial.write_all(line).await.unwrap();
+    }
+}
+
+async fn toggle(mut port: AsyncPort1, mut led: P1_0<Output<PushPull>>) {
+    loop {
+        if port.wait().await.contains(3) {
+            led.toggle().unwrap();
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    let _wdt = WatchdogTimer::new(p.WATCHDOG_TIMER).disable();
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let clocks = ClockConfig::new(p.SYSTEM_CLOCK)
+        .dco_calibrated(DcoFrequency::Mhz1)
+        .aclk_vlo()
+        .aclk_divider(AclkDivider::By2)
+        .freeze(&mut sfr.osc_fault);
+
+    let pins = p.PORT_1_2.split();
+    let led = pins.p1_0.into_push_pull_output();
+    let mut button = pins.p1_3.into_pull_up_input();
+    button.enable_interrupt(Edge::Falling);
+
+    let mut timer = Timer::new(p.TIMER0_A3);
+    timer.start_ms(&clocks, 1000).unwrap();
+    let serial = Serial::new(p.USCI_A0_UART_MODE, pins.p1_2, pins.p1_1, &clocks);
+    let i2c = I2c::new(p.USCI_B0_I2C_MODE, pins.p1_6, pins.p1_7, sfr.ucb0ifg, &clocks);
+
+    let mut tasks: [Task; 2] = [
+        pin!(report(
+            AsyncTimer::new(timer),
+            AsyncSerial::new(serial),
+            AsyncI2c::new(i2c)
+        )),
+        pin!(toggle(AsyncPort1::new(pins.int1), led)),
+    ];
+
+    // The UART and I2C run off SMCLK, which must keep running.
+    executor::run(&mut tasks, LowPowerMode::Lpm0);
+}
+
+wake_interrupt!(TIMER0_A1, asynch::timer_interrupt);
+wake_interrupt!(USCIAB0TX, asynch::usci_tx_interrupt);
+wake_interrupt!(USCIAB0RX, asynch::usci_rx_interrupt);
+wake_interrupt!(PORT1, asynch::port1_interrupt);
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
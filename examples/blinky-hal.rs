@@ -0,0 +1,69 @@
+//! The [blinky] demo, rewritten to use the type-state GPIO API from the [temp-hal] example's HAL
+//! instead of manipulating `PORT_1_2` registers by hand.
+//!
+//! Each pin is a separate type whose configuration is part of the type, so e.g. calling
+//! `toggle()` on a pin that is still an input is a compile error.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::gpio::GpioExt;
+
+extern crate panic_msp430;
+
+use embedded_hal::digital::blocking::{OutputPin, ToggleableOutputPin};
+use msp430::asm;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+fn delay(n: u16) {
+    let mut i = 0;
+    loop {
+        asm::nop();
+
+        i += 1;
+
+        if i == n {
+            break;
+        }
+    }
+}
+
+// P0 = red LED
+// P6 = green LED
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    // Disable watchdog
+    let wd = p.WATCHDOG_TIMER;
+    wd.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let pins = p.PORT_1_2.split();
+    let mut red = pins.p1_0.into_push_pull_output();
+    let mut green = pins.p1_6.into_push_pull_output();
+
+    // set P0 high and P6 low
+    red.set_high().unwrap();
+    green.set_low().unwrap();
+
+    loop {
+        delay(10_000);
+
+        // toggle outputs
+        red.toggle().unwrap();
+        green.toggle().unwrap();
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
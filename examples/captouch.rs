@@ -0,0 +1,82 @@
+//! Capacitive touch sensing with the PinOsc feature of the G2xx3 parts, no external
+//! components required.
+//!
+//! Connect four electrodes arranged in a circle (a wheel) to P2.1 through P2.4, and a button
+//! electrode to P2.5. On the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development
+//! kit, touching the button toggles the red LED, and the green LED is lit while the wheel is
+//! touched on its "upper half".
+//!
+//! This example shares the HAL from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::captouch::{CapTouch, TouchButton, TouchEvent, Wheel};
+use hal::gpio::GpioExt;
+use hal::{SfrIfg, WatchdogDivider};
+
+extern crate panic_msp430;
+
+use embedded_hal::digital::blocking::{OutputPin, ToggleableOutputPin};
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+// Counts below the baseline that register as a touch. Depends heavily on electrode size and
+// overlay thickness; print raw counts from `CapTouch::measure` to tune.
+const THRESHOLD: u16 = 100;
+const WHEEL_RESOLUTION: u16 = 64;
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    let sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let mut ct = CapTouch::new(
+        p.TIMER0_A3,
+        p.WATCHDOG_TIMER,
+        sfr.wdtifg,
+        WatchdogDivider::By512,
+    );
+
+    let pins = p.PORT_1_2.split();
+    let mut red = pins.p1_0.into_push_pull_output();
+    let mut green = pins.p1_6.into_push_pull_output();
+
+    let mut wheel = Wheel::new(
+        [
+            pins.p2_1.into_pin_osc(),
+            pins.p2_2.into_pin_osc(),
+            pins.p2_3.into_pin_osc(),
+            pins.p2_4.into_pin_osc(),
+        ],
+        THRESHOLD,
+        WHEEL_RESOLUTION,
+    );
+    let mut button = TouchButton::new(pins.p2_5.into_pin_osc(), THRESHOLD);
+
+    wheel.calibrate(&mut ct);
+    button.calibrate(&mut ct);
+
+    loop {
+        if button.scan(&mut ct) == Some(TouchEvent::Touch) {
+            red.toggle().unwrap();
+        }
+
+        wheel.scan(&mut ct);
+        match wheel.position() {
+            Some(pos) if pos < WHEEL_RESOLUTION / 2 => green.set_high().unwrap(),
+            _ => green.set_low().unwrap(),
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
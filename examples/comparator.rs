@@ -0,0 +1,105 @@
+//! Comparator_A+ threshold detection and frequency measurement.
+//!
+//! Compares the voltage on P1.4 (CA4) against 0.5 Vcc. On the
+//! [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development kit, the red LED follows the
+//! comparator output, updated from the comparator interrupt. Rising edges of the output are also
+//! captured by TIMER0_A3 through its internal CAOUT connection, and the green LED is lit while
+//! the signal on P1.4 is faster than 1 kHz.
+//!
+//! This example shares the HAL from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(abi_msp430_interrupt)]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::comparator::{CaptureEdge, Comparator, InternalRef, Terminal};
+use hal::gpio::{self, Edge, GpioExt};
+
+extern crate panic_msp430;
+
+use core::cell::RefCell;
+use embedded_hal::digital::blocking::OutputPin;
+use msp430::interrupt as mspint;
+use msp430_rt::entry;
+use {{device}}::{interrupt, Peripherals};
+
+type RedLed = gpio::P1_0<gpio::Output<gpio::PushPull>>;
+type GreenLed = gpio::P1_6<gpio::Output<gpio::PushPull>>;
+
+static COMPARATOR: mspint::Mutex<RefCell<Option<(Comparator, RedLed)>>> =
+    mspint::Mutex::new(RefCell::new(None));
+
+// SMCLK counts between rising edges at 1 kHz, with the default ~1.1 MHz DCO.
+const PERIOD_1KHZ: u16 = 1100;
+
+fn init(cs: mspint::CriticalSection) -> ({{device}}::TIMER0_A3, GreenLed) {
+    let p = Peripherals::take().unwrap();
+
+    let wdt = &p.WATCHDOG_TIMER;
+    wdt.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let pins = p.PORT_1_2.split();
+    let red = pins.p1_0.into_push_pull_output();
+    let green = pins.p1_6.into_push_pull_output();
+    let mut input = pins.p1_4;
+
+    let mut ca = Comparator::new(p.COMPARATOR_A);
+    ca.connect_minus(&mut input);
+    ca.set_reference(InternalRef::HalfVcc, Terminal::Plus);
+    ca.set_filter(true);
+    ca.enable();
+    // CAOUT is high while P1.4 is below 0.5 Vcc; a rising input is a falling output.
+    ca.listen(if ca.output() { Edge::Falling } else { Edge::Rising });
+
+    let timer = p.TIMER0_A3;
+    timer
+        .tactl
+        .write(|w| w.tassel().tassel_2().mc().mc_2().taclr().set_bit());
+    ca.capture_on(&timer, CaptureEdge::Rising, false);
+
+    *COMPARATOR.borrow(cs).borrow_mut() = Some((ca, red));
+    (timer, green)
+}
+
+#[entry(interrupt_enable(pre_interrupt = init))]
+fn main(periph: ({{device}}::TIMER0_A3, GreenLed)) -> ! {
+    let (timer, mut green) = periph;
+    let mut last = None;
+
+    loop {
+        match Comparator::take_capture(&timer) {
+            Some(Ok(count)) => {
+                if let Some(prev) = last {
+                    let period: u16 = count.wrapping_sub(prev);
+                    green.set_state((period < PERIOD_1KHZ).into()).unwrap();
+                }
+                last = Some(count);
+            }
+            // Missed an edge; the next period would be wrong.
+            Some(Err(_)) => last = None,
+            None => {}
+        }
+    }
+}
+
+#[interrupt]
+fn COMPARATORA(cs: CriticalSection) {
+    let mut c_ref = COMPARATOR.borrow(cs).borrow_mut();
+    let (ca, red) = c_ref.as_mut().unwrap();
+
+    let high = ca.output();
+    red.set_state(high.into()).unwrap();
+    ca.listen(if high { Edge::Falling } else { Edge::Rising });
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
@@ -0,0 +1,117 @@
+//! Starting a 32.768 kHz watch crystal on LFXT1, with fallback to the internal VLO.
+//!
+//! The [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development kit ships with the
+//! crystal _unsoldered_. If the crystal starts, the green LED blinks once per second using ACLK.
+//! If it doesn't start (or stops later on and the oscillator fault NMI fires), ACLK is switched
+//! to the VLO and the red LED blinks instead.
+//!
+//! This example shares the HAL from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(abi_msp430_interrupt)]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::{clock, SfrIfg};
+
+extern crate panic_msp430;
+
+use core::cell::{Cell, RefCell};
+use msp430::{interrupt as mspint, critical_section as mspcs};
+use msp430_rt::entry;
+use {{device}}::{interrupt, Peripherals};
+use once_cell::unsync::OnceCell;
+
+static OSC_FAULT: mspint::Mutex<RefCell<Option<clock::OscFault>>> =
+    mspint::Mutex::new(RefCell::new(None));
+static PORT1_PINS: mspint::Mutex<OnceCell<{{device}}::PORT_1_2>> =
+    mspint::Mutex::new(OnceCell::new());
+static TIMER: mspint::Mutex<OnceCell<{{device}}::TIMER0_A3>> =
+    mspint::Mutex::new(OnceCell::new());
+static ON_CRYSTAL: mspint::Mutex<Cell<bool>> = mspint::Mutex::new(Cell::new(false));
+
+fn init(cs: mspint::CriticalSection) {
+    let p = Peripherals::take().unwrap();
+
+    let wdt = &p.WATCHDOG_TIMER;
+    wdt.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let port_1_2 = &p.PORT_1_2;
+    port_1_2
+        .p1dir
+        .modify(|_, w| w.p0().set_bit().p6().set_bit());
+    port_1_2
+        .p1out
+        .modify(|_, w| w.p0().clear_bit().p6().clear_bit());
+
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+
+    // The crystal on the MSP-EXP430G2 wants 12.5 pF of load capacitance.
+    let clocks = clock::ClockConfig::new(p.SYSTEM_CLOCK)
+        .aclk_lfxt1(clock::Lfxt1Cap::Cap12_5pF)
+        .freeze(&mut sfr.osc_fault);
+
+    // Toggle twice a second.
+    let timer = &p.TIMER0_A3;
+    timer
+        .taccr0
+        .write(|w| w.bits((clocks.aclk() / 2) as u16));
+    timer.tactl.modify(|_, w| w.tassel().tassel_1().mc().mc_1());
+    timer.tacctl1.modify(|_, w| w.ccie().set_bit());
+
+    ON_CRYSTAL.borrow(cs).set(!clocks.lfxt1_fault());
+    *OSC_FAULT.borrow(cs).borrow_mut() = Some(sfr.osc_fault);
+    PORT1_PINS.borrow(cs).set(p.PORT_1_2).ok().unwrap();
+    TIMER.borrow(cs).set(p.TIMER0_A3).ok().unwrap();
+}
+
+#[entry(interrupt_enable(pre_interrupt = init))]
+fn main() -> ! {
+    loop {
+        mspcs::with(|_cs| {
+            // Do something while interrupts are disabled.
+        })
+    }
+}
+
+#[interrupt]
+fn NMI(cs: CriticalSection) {
+    let mut f_ref = OSC_FAULT.borrow(cs).borrow_mut();
+
+    if f_ref.as_mut().unwrap().handle_nmi() {
+        // Now running from the VLO; retime the blink for its (nominal) frequency.
+        let timer = TIMER.borrow(cs).get().unwrap();
+        timer
+            .taccr0
+            .write(|w| w.bits((clock::VLO_NOMINAL_HZ / 2) as u16));
+
+        ON_CRYSTAL.borrow(cs).set(false);
+
+        let port_1_2 = PORT1_PINS.borrow(cs).get().unwrap();
+        port_1_2.p1out.modify(|_, w| w.p6().clear_bit());
+    }
+}
+
+#[interrupt]
+fn TIMER0_A1(cs: CriticalSection) {
+    let timer = TIMER.borrow(cs).get().unwrap();
+    timer.tacctl1.modify(|_, w| w.ccifg().clear_bit());
+
+    let port_1_2 = PORT1_PINS.borrow(cs).get().unwrap();
+    if ON_CRYSTAL.borrow(cs).get() {
+        port_1_2.p1out.modify(|r, w| w.p6().bit(!r.p6().bit()));
+    } else {
+        port_1_2.p1out.modify(|r, w| w.p0().bit(!r.p0().bit()));
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
@@ -0,0 +1,102 @@
+//! Keeping a large constant table in the `.far_rodata` section.
+//!
+//! Computes the CRC-16/CCITT of a message with a 512-byte lookup table and prints it over the
+//! HW UART (9600 baud) of the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development
+//! kit. On the msp430g2553 the table simply lives in ROM. On parts with flash above the 64kB
+//! mark it lives there instead, e.g. on the msp430f5529 with `memory-f5529.x` copied over
+//! `memory.x`, leaving the first 64kB to code; the example itself is unchanged. Adapt the clock
+//! and serial setup when running on such a part, since the HAL is written for the G2xx3.
+//!
+//! This example shares the HAL from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::clock::ClockConfig;
+use hal::gpio::GpioExt;
+use hal::tlv::DcoFrequency;
+use hal::{Serial, SfrIfg, WatchdogTimer};
+
+extern crate panic_msp430;
+
+use core::fmt::Write;
+
+use embedded_hal::serial::{self, nb::Write as SerWrite};
+use msp430::asm;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+const MESSAGE: &[u8] = b"123456789";
+
+const fn crc_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+far_data! {
+    static CRC_TABLE: [u16; 256] = crc_table();
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &b| {
+        let index = usize::from((crc >> 8) as u8 ^ b);
+        (crc << 8) ^ CRC_TABLE.get(index).unwrap()
+    })
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    let _wdt = WatchdogTimer::new(p.WATCHDOG_TIMER).disable();
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let clocks = ClockConfig::new(p.SYSTEM_CLOCK)
+        .dco_calibrated(DcoFrequency::Mhz1)
+        .freeze(&mut sfr.osc_fault);
+
+    let pins = p.PORT_1_2.split();
+    let mut serial = Serial::new(p.USCI_A0_UART_MODE, pins.p1_2, pins.p1_1, &clocks);
+
+    // Expected: 0x29b1.
+    let s: &mut dyn SerWrite<Error = serial::ErrorKind> = &mut serial;
+    writeln!(
+        s,
+        "table at {:#07x}, crc {:#06x}",
+        CRC_TABLE.addr(),
+        crc16(MESSAGE)
+    )
+    .unwrap();
+
+    loop {
+        asm::nop();
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
@@ -0,0 +1,84 @@
+//! Locking the DCO to an arbitrary frequency with a software FLL, using a 32.768 kHz watch
+//! crystal as the reference.
+//!
+//! Requires a crystal soldered onto the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2)
+//! development kit. The DCO is locked to 4 MHz, for which there are no factory calibration
+//! constants, and re-trimmed every few blinks to follow temperature drift. The green LED blinks
+//! while locked; the red LED lights up if locking failed.
+//!
+//! This example shares the HAL from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::{clock, fll, SfrIfg};
+
+extern crate panic_msp430;
+
+use msp430::asm;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+fn delay(n: u16) {
+    for _ in 0..n {
+        asm::nop();
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    let wdt = &p.WATCHDOG_TIMER;
+    wdt.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let port_1_2 = &p.PORT_1_2;
+    port_1_2
+        .p1dir
+        .modify(|_, w| w.p0().set_bit().p6().set_bit());
+    port_1_2
+        .p1out
+        .modify(|_, w| w.p0().clear_bit().p6().clear_bit());
+
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let mut clocks = clock::ClockConfig::new(p.SYSTEM_CLOCK)
+        .aclk_lfxt1(clock::Lfxt1Cap::Cap12_5pF)
+        .freeze(&mut sfr.osc_fault);
+
+    let timer = &p.TIMER0_A3;
+    let locked = fll::Fll::new(4_000_000, &clocks)
+        .and_then(|mut fll| fll.lock(timer, &mut clocks).map(|_| fll));
+
+    let mut fll = match locked {
+        Ok(fll) => fll,
+        Err(_) => {
+            port_1_2.p1out.modify(|_, w| w.p0().set_bit());
+            loop {
+                asm::nop();
+            }
+        }
+    };
+
+    loop {
+        for _ in 0..8 {
+            delay(50_000);
+            port_1_2.p1out.modify(|r, w| w.p6().bit(!r.p6().bit()));
+        }
+
+        if fll.trim(timer, &mut clocks).is_err() {
+            port_1_2.p1out.modify(|_, w| w.p0().set_bit());
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
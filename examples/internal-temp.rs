@@ -0,0 +1,87 @@
+//! Reading the on-chip temperature sensor and supply voltage, no external components required.
+//!
+//! Prints the die temperature and Vcc over the HW UART (9600 baud) of the
+//! [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development kit about once a second.
+//! The temperature is followed by `*` if it is uncalibrated because segment A holds no factory
+//! calibration. The red LED lights up while Vcc is below 2.7V.
+//!
+//! This example shares the HAL and formatting from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::adc::{Adc10, Reference, SampleTime};
+use hal::clock::ClockConfig;
+use hal::gpio::GpioExt;
+use hal::supply::{self, SupplyMonitor};
+use hal::temp_sensor::InternalTemperature;
+use hal::tlv::DcoFrequency;
+use hal::{Serial, SfrIfg, WatchdogTimer};
+
+#[path = "temp-hal/newtypes.rs"]
+#[allow(unused)]
+mod newtypes;
+
+extern crate panic_msp430;
+
+use core::fmt::Write;
+
+use embedded_hal::digital::blocking::OutputPin;
+use embedded_hal::serial::{self, nb::Write as SerWrite};
+use msp430::asm;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+const LOW_BATTERY_MV: u16 = 2700;
+
+fn delay(n: u16) {
+    for _ in 0..n {
+        asm::nop();
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    let _wdt = WatchdogTimer::new(p.WATCHDOG_TIMER).disable();
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let clocks = ClockConfig::new(p.SYSTEM_CLOCK)
+        .dco_calibrated(DcoFrequency::Mhz1)
+        .freeze(&mut sfr.osc_fault);
+
+    let pins = p.PORT_1_2.split();
+    let mut red = pins.p1_0.into_push_pull_output();
+    let mut serial = Serial::new(p.USCI_A0_UART_MODE, pins.p1_2, pins.p1_1, &clocks);
+
+    let mut adc = Adc10::new(p.ADC10, Reference::Vcc, SampleTime::Cycles4, &clocks);
+    let mut sensor = InternalTemperature::new();
+    let mark = if sensor.is_calibrated() { "" } else { "*" };
+    let mut monitor = SupplyMonitor::new(LOW_BATTERY_MV);
+
+    loop {
+        let tmp: newtypes::fmt::I8F8SmallFmt = sensor.read(&mut adc).into();
+        let mv = supply::supply_voltage(&mut adc);
+
+        monitor.update(mv);
+        red.set_state(monitor.is_low().into()).unwrap();
+
+        let s: &mut dyn SerWrite<Error = serial::ErrorKind> = &mut serial;
+        writeln!(s, "{} C{}, {} mV", tmp, mark, mv).unwrap();
+
+        for _ in 0..10 {
+            delay(20000);
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
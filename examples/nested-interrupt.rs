@@ -0,0 +1,134 @@
+//! A slow interrupt handler that doesn't delay the others, using `nested_interrupt!` from the
+//! [temp-hal] example's HAL, on the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2)
+//! development kit.
+//!
+//! Bytes received over the HW UART (9600 baud) are echoed back after some deliberately slow
+//! processing. The UART receive handler re-enables interrupts once it has read the byte, so the
+//! timer interrupt blinking the red LED keeps its pace while it runs. With `#[interrupt]`
+//! instead, the blinking stutters as you type. Bytes arriving while one is still being
+//! processed are dropped.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(abi_msp430_interrupt)]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::clock::{AclkDivider, ClockConfig};
+use hal::gpio::{GpioExt, Output, PushPull, P1_0};
+use hal::lpm::{self, LowPowerMode};
+use hal::nested::Nested;
+use hal::shared::Shared;
+use hal::tlv::DcoFrequency;
+use hal::{Serial, SfrIfg, Timer, WatchdogTimer};
+
+extern crate panic_msp430;
+
+use embedded_hal::digital::blocking::{OutputPin, ToggleableOutputPin};
+use embedded_hal::serial::nb::Write as SerWrite;
+use msp430::{asm, interrupt as mspint};
+use msp430_rt::entry;
+use {{device}}::{interrupt, Peripherals, SPECIAL_FUNCTION, USCI_A0_UART_MODE};
+
+// IE2 bit.
+const UCA0RXIE: u8 = 0x01;
+
+static TIMER: Shared<Timer> = Shared::uninit();
+static SERIAL: Shared<Serial> = Shared::uninit();
+static LED: Shared<P1_0<Output<PushPull>>> = Shared::uninit();
+
+fn ie2_modify(f: impl FnOnce(u8) -> u8) {
+    // SAFETY: Only UCA0RXIE is changed, by the UART receive handler and `init` before it.
+    let sfr = unsafe { &*SPECIAL_FUNCTION::ptr() };
+    sfr.ie2.modify(|r, w| unsafe { w.bits(f(r.bits())) });
+}
+
+fn delay(n: u16) {
+    let mut i = 0;
+    loop {
+        asm::nop();
+
+        i += 1;
+
+        if i == n {
+            break;
+        }
+    }
+}
+
+fn init(cs: mspint::CriticalSection) {
+    let p = Peripherals::take().unwrap();
+
+    let _wdt = WatchdogTimer::new(p.WATCHDOG_TIMER).disable();
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let clocks = ClockConfig::new(p.SYSTEM_CLOCK)
+        .dco_calibrated(DcoFrequency::Mhz1)
+        .aclk_vlo()
+        .aclk_divider(AclkDivider::By2)
+        .freeze(&mut sfr.osc_fault);
+
+    let pins = p.PORT_1_2.split();
+    let mut led = pins.p1_0.into_push_pull_output();
+    led.set_high().unwrap();
+
+    let mut timer = Timer::new(p.TIMER0_A3);
+    timer.start_ms(&clocks, 100).unwrap();
+
+    let serial = Serial::new(p.USCI_A0_UART_MODE, pins.p1_2, pins.p1_1, &clocks);
+    ie2_modify(|ie2| ie2 | UCA0RXIE);
+
+    // SAFETY: Interrupts are still disabled, and each is only initialized here.
+    unsafe {
+        TIMER.init(cs, timer);
+        SERIAL.init(cs, serial);
+        LED.init(cs, led);
+    }
+}
+
+#[entry(interrupt_enable(pre_interrupt = init))]
+fn main() -> ! {
+    // Everything happens in interrupt handlers. The UART runs off SMCLK, which must keep running.
+    loop {
+        lpm::enter(LowPowerMode::Lpm0);
+    }
+}
+
+#[interrupt]
+fn TIMER0_A1(cs: CriticalSection) {
+    // SAFETY: Initialized in `init`, before interrupts were enabled, and not nested.
+    unsafe {
+        TIMER.with(cs, |t| t.timer_int());
+        LED.with(cs, |led| led.toggle().unwrap());
+    }
+}
+
+nested_interrupt!(USCIAB0RX, receive, process);
+
+fn receive(_cs: mspint::CriticalSection) -> u8 {
+    // Keeps the handler from nesting within itself; `process` re-enables it when done.
+    ie2_modify(|ie2| ie2 & !UCA0RXIE);
+
+    // SAFETY: Only this handler reads from the UART. Reading clears the interrupt flag.
+    let uart = unsafe { &*USCI_A0_UART_MODE::ptr() };
+    uart.uca0rxbuf.read().bits()
+}
+
+fn process(byte: u8, nested: Nested) {
+    // Stand-in for real work, e.g. parsing a command.
+    delay(20_000);
+
+    nested.lock(|cs| {
+        // SAFETY: As above.
+        unsafe { SERIAL.with(cs, |s| nb::block!(s.write(byte)).unwrap()) };
+        ie2_modify(|ie2| ie2 | UCA0RXIE);
+    });
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
@@ -0,0 +1,103 @@
+//! Keeping state in RAM across warm resets with the `.noinit` section.
+//!
+//! Counts resets by cause, and prints the counts over the HW UART (9600 baud) of the
+//! [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development kit. Press the RST button
+//! for a reset pin reset, or the button on P1.3 for a watchdog reset. The counts survive both,
+//! but start over from zero when the board loses power.
+//!
+//! This example shares the HAL from the [temp-hal] example.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::clock::ClockConfig;
+use hal::gpio::GpioExt;
+use hal::noinit::{NoInit, ResetCause};
+use hal::tlv::DcoFrequency;
+use hal::{Serial, SfrIfg};
+
+extern crate panic_msp430;
+
+use core::fmt::Write;
+use core::ptr;
+
+use embedded_hal::digital::blocking::InputPin;
+use embedded_hal::serial::{self, nb::Write as SerWrite};
+use msp430::critical_section as mspcs;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+// No padding, so all of it is covered by the checksum.
+#[derive(Clone, Copy, Default)]
+struct ResetCounts {
+    reset_pin: u16,
+    watchdog: u16,
+    other: u16,
+}
+
+#[link_section = ".noinit"]
+static COUNTS: NoInit<ResetCounts> = NoInit::new();
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    p.WATCHDOG_TIMER
+        .wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+    let cause = sfr.reset_flags.take_cause();
+
+    let counts = mspcs::with(|cs| {
+        let mut counts = match cause {
+            // Whatever is in RAM is garbage, even if it happens to pass the checksum.
+            ResetCause::PowerOn => ResetCounts::default(),
+            _ => COUNTS.get(cs).unwrap_or_default(),
+        };
+
+        match cause {
+            ResetCause::PowerOn => {}
+            ResetCause::ResetPin => counts.reset_pin += 1,
+            ResetCause::Watchdog => counts.watchdog += 1,
+            ResetCause::Other => counts.other += 1,
+        }
+
+        COUNTS.set(cs, counts);
+        counts
+    });
+
+    let clocks = ClockConfig::new(p.SYSTEM_CLOCK)
+        .dco_calibrated(DcoFrequency::Mhz1)
+        .freeze(&mut sfr.osc_fault);
+
+    let pins = p.PORT_1_2.split();
+    let mut serial = Serial::new(p.USCI_A0_UART_MODE, pins.p1_2, pins.p1_1, &clocks);
+    let button = pins.p1_3.into_pull_up_input();
+
+    let s: &mut dyn SerWrite<Error = serial::ErrorKind> = &mut serial;
+    writeln!(
+        s,
+        "{:?}: {} pin, {} watchdog, {} other",
+        cause, counts.reset_pin, counts.watchdog, counts.other
+    )
+    .unwrap();
+
+    loop {
+        if button.is_low().unwrap() {
+            // SAFETY: Deliberately writing WDTCTL without the password, which resets the part.
+            unsafe { ptr::write_volatile(0x0120 as *mut u16, 0) };
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
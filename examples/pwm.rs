@@ -0,0 +1,81 @@
+//! Fades two LEDs in and out with hardware PWM from TIMER1_A3, using the [temp-hal] example's
+//! HAL, on the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development kit.
+//!
+//! The on-board LEDs aren't on timer outputs, so connect LEDs (with series resistors) from P2.1
+//! (TA1.1) and P2.4 (TA1.2) to GND. One brightens while the other dims. The PWM runs at ~1.1 kHz
+//! off the default DCO, fast enough not to flicker; the CPU only updates the duty cycles.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::gpio::GpioExt;
+use hal::pwm::Pwm;
+
+extern crate panic_msp430;
+
+use msp430::asm;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+// SMCLK counts per PWM period, less one.
+const PERIOD: u16 = 999;
+// Duty cycle change per step; a full fade takes PERIOD / STEP steps.
+const STEP: u16 = 5;
+
+fn delay(n: u16) {
+    let mut i = 0;
+    loop {
+        asm::nop();
+
+        i += 1;
+
+        if i == n {
+            break;
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    // Disable watchdog
+    let wd = p.WATCHDOG_TIMER;
+    wd.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let pins = p.PORT_1_2.split();
+
+    let mut pwm = Pwm::new(p.TIMER1_A3, PERIOD);
+    let mut rising = pwm.channel1(pins.p2_1);
+    let mut falling = pwm.channel2(pins.p2_4);
+    let max = rising.max_duty();
+
+    let mut duty = 0;
+    let mut up = true;
+
+    loop {
+        rising.set_duty(duty);
+        falling.set_duty(max - duty);
+        delay(1_000);
+
+        if up {
+            duty = (duty + STEP).min(max);
+            up = duty < max;
+        } else {
+            duty = duty.saturating_sub(STEP);
+            up = duty == 0;
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
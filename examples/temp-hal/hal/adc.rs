@@ -0,0 +1,401 @@
+// ADC10 driver. Conversions are triggered in software (ADC10SC) and clocked by ADC10OSC.
+//
+// Channels are types: P1.0 through P1.7 are A0 through A7, and the internal channels have
+// their own types. Multi-channel sequences on the ADC10 always run from the selected channel
+// down to A0, so a sequence takes over every analog input below its highest channel as well.
+//
+// Sequences and repeated conversions are moved into RAM by the Data Transfer Controller (DTC),
+// either blocking (`read_into`) or in the background with completion signalled by the ADC10
+// interrupt (`start`).
+
+use core::cell::Cell;
+
+use msp430::{interrupt as mspint, critical_section as mspcs};
+
+use super::clock::{self, Clocks};
+use super::gpio::{P1_0, P1_1, P1_2, P1_3, P1_4, P1_5, P1_6, P1_7};
+
+// Reference turn-on time.
+const REF_SETTLE_US: u32 = 30;
+
+static DTC_DONE: mspint::Mutex<Cell<bool>> = mspint::Mutex::new(Cell::new(false));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(unused)]
+pub enum Reference {
+    // VR+ = Vcc, VR- = Vss
+    Vcc,
+    Internal1_5V,
+    Internal2_5V,
+}
+
+// Sample-and-hold time in ADC10CLK cycles (ADC10SHTx).
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum SampleTime {
+    Cycles4 = 0,
+    Cycles8 = 1,
+    Cycles16 = 2,
+    Cycles64 = 3,
+}
+
+// ADC10CLK divider (ADC10DIVx) applied to ADC10OSC.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum ClockDivider {
+    By1 = 0,
+    By2 = 1,
+    By3 = 2,
+    By4 = 3,
+    By5 = 4,
+    By6 = 5,
+    By7 = 6,
+    By8 = 7,
+}
+
+// Conversion sequence mode (CONSEQx).
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(unused)]
+pub enum Mode {
+    Single = 0,
+    // Channel INCHx down to A0, once.
+    Sequence = 1,
+    RepeatSingle = 2,
+    RepeatSequence = 3,
+}
+
+pub trait Channel {
+    // INCHx
+    const CHANNEL: u8;
+}
+
+macro_rules! analog_pins {
+    ($($PXi:ident: $ch:expr,)+) => {
+        $(
+            impl<MODE> Channel for $PXi<MODE> {
+                const CHANNEL: u8 = $ch;
+            }
+        )+
+    };
+}
+
+analog_pins!(
+    P1_0: 0,
+    P1_1: 1,
+    P1_2: 2,
+    P1_3: 3,
+    P1_4: 4,
+    P1_5: 5,
+    P1_6: 6,
+    P1_7: 7,
+);
+
+// Internal temperature sensor.
+pub struct Temperature;
+// (Vcc - Vss) / 2
+pub struct HalfVcc;
+
+impl Channel for Temperature {
+    const CHANNEL: u8 = 10;
+}
+
+impl Channel for HalfVcc {
+    const CHANNEL: u8 = 11;
+}
+
+// Number of results the DTC writes for a buffer of `len`. ADC10IFG is only set once the whole
+// block is transferred, so the block must not be longer than the conversions started: one for
+// `Single`, and one per channel down to A0 for `Sequence`. The repeat modes convert until
+// stopped, up to the DTC's limit of 255 transfers per block. The rest of a longer buffer is left
+// untouched.
+fn block_len(channel: u8, mode: Mode, len: usize) -> usize {
+    let max = match mode {
+        Mode::Single => 1,
+        Mode::Sequence => usize::from(channel) + 1,
+        Mode::RepeatSingle | Mode::RepeatSequence => 255,
+    };
+
+    len.min(max)
+}
+
+pub struct Adc10 {
+    inner: {{device}}::ADC10,
+    div: ClockDivider,
+    // `REF_SETTLE_US` at the MCLK frequency given to `new`.
+    ref_settle_loops: u16,
+}
+
+#[allow(unused)]
+impl Adc10 {
+    pub fn new(
+        inner: {{device}}::ADC10,
+        reference: Reference,
+        sample_time: SampleTime,
+        clocks: &Clocks,
+    ) -> Self {
+        let adc = Adc10 {
+            inner,
+            div: ClockDivider::By1,
+            ref_settle_loops: clock::delay_loops_for_us(clocks, REF_SETTLE_US),
+        };
+        adc.configure(reference, sample_time);
+        adc
+    }
+
+    // Change the reference and sample time. The internal reference needs ~30us to settle
+    // after being switched on; wait before converting.
+    pub fn configure(&self, reference: Reference, sample_time: SampleTime) {
+        self.inner.adc10ctl0.modify(|_, w| w.enc().clear_bit());
+        self.inner.adc10ctl0.write(|w| {
+            let w = w.adc10sht().bits(sample_time as u8).adc10on().set_bit();
+
+            match reference {
+                Reference::Vcc => w.sref().sref_0(),
+                Reference::Internal1_5V => w.sref().sref_1().refon().set_bit(),
+                Reference::Internal2_5V => {
+                    w.sref().sref_1().refon().set_bit().ref2_5v().set_bit()
+                }
+            }
+        });
+    }
+
+    // `configure`, but also waits for the internal reference to settle if it was just turned on
+    // or switched between 1.5V and 2.5V.
+    pub fn configure_settled(&self, reference: Reference, sample_time: SampleTime) {
+        let prev = self.reference();
+
+        self.configure(reference, sample_time);
+        if reference != Reference::Vcc && reference != prev {
+            clock::delay_loops(self.ref_settle_loops);
+        }
+    }
+
+    // ADC10OSC runs at ~3.7-6.3 MHz; divide it down when the sample time must be longer than
+    // 64 cycles allows. Takes effect from the next conversion.
+    pub fn set_clock_divider(&mut self, div: ClockDivider) {
+        self.div = div;
+    }
+
+    pub fn reference(&self) -> Reference {
+        let ctl0 = self.inner.adc10ctl0.read();
+
+        if !ctl0.refon().bit_is_set() {
+            Reference::Vcc
+        } else if ctl0.ref2_5v().bit_is_set() {
+            Reference::Internal2_5V
+        } else {
+            Reference::Internal1_5V
+        }
+    }
+
+    // Single conversion, blocking.
+    pub fn read<C: Channel>(&mut self, _channel: &mut C) -> u16 {
+        self.select(C::CHANNEL, Mode::Single);
+        self.inner.adc10dtc1.write(|w| unsafe { w.bits(0) }); // DTC off.
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.adc10ifg().clear_bit().enc().set_bit().adc10sc().set_bit());
+
+        while self.inner.adc10ctl1.read().adc10busy().bit_is_set() {}
+
+        self.inner.adc10ctl0.modify(|_, w| w.enc().clear_bit());
+        self.inner.adc10mem.read().bits()
+    }
+
+    // Start a single conversion and return immediately. With `interrupt`, ADC10 fires once it
+    // completes; collect the result with `take_result`. Not to be mixed with `start`, which
+    // uses the same interrupt.
+    pub fn start_single<C: Channel>(&mut self, _channel: &mut C, interrupt: bool) {
+        self.select(C::CHANNEL, Mode::Single);
+        self.inner.adc10dtc1.write(|w| unsafe { w.bits(0) }); // DTC off.
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.adc10ifg().clear_bit().adc10ie().bit(interrupt));
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.enc().set_bit().adc10sc().set_bit());
+    }
+
+    // Result of a conversion from `start_single`, if it has completed. May be called from the
+    // `ADC10` interrupt handler.
+    pub fn take_result(_cs: mspint::CriticalSection) -> Option<u16> {
+        // SAFETY: Only ENC, the interrupt enable and flag, and the result register are touched,
+        // after the conversion started by `start_single` is over.
+        let adc = unsafe { &*{{device}}::ADC10::ptr() };
+
+        if adc.adc10ctl0.read().adc10ifg().bit_is_clear() {
+            return None;
+        }
+
+        adc.adc10ctl0.modify(|_, w| {
+            w.enc()
+                .clear_bit()
+                .adc10ie()
+                .clear_bit()
+                .adc10ifg()
+                .clear_bit()
+        });
+        Some(adc.adc10mem.read().bits())
+    }
+
+    // Fill `buf` using the DTC, blocking, and return the number of results written; see
+    // `block_len`. For the sequence modes, results are for channels `channel`, `channel - 1`,
+    // ..., A0, repeated for `RepeatSequence`.
+    pub fn read_into<C: Channel>(
+        &mut self,
+        _channel: &mut C,
+        mode: Mode,
+        buf: &mut [u16],
+    ) -> usize {
+        let len = self.start_dtc(C::CHANNEL, mode, buf.as_mut_ptr(), buf.len(), false);
+        if len == 0 {
+            return 0;
+        }
+
+        while self.inner.adc10ctl0.read().adc10ifg().bit_is_clear() {}
+
+        self.stop();
+        len
+    }
+
+    // Fill `buf` in the background, as for `read_into`, with the ADC10 interrupt signalling
+    // completion. Call `on_interrupt` from the `ADC10` handler.
+    pub fn start<C: Channel>(
+        mut self,
+        _channel: &mut C,
+        mode: Mode,
+        buf: &'static mut [u16],
+    ) -> Transfer {
+        mspcs::with(|cs| DTC_DONE.borrow(cs).set(false));
+        let len = self.start_dtc(C::CHANNEL, mode, buf.as_mut_ptr(), buf.len(), true);
+        if len == 0 {
+            mspcs::with(|cs| DTC_DONE.borrow(cs).set(true));
+        }
+
+        Transfer {
+            adc: self,
+            buf,
+            len,
+        }
+    }
+
+    fn select(&mut self, channel: u8, mode: Mode) {
+        self.inner.adc10ctl0.modify(|_, w| w.enc().clear_bit());
+
+        // Analog-enable the external inputs used by this conversion: just the channel itself
+        // for single-channel modes, and every channel down to A0 for sequences.
+        let ae = match (mode, channel) {
+            (Mode::Single, 0..=7) | (Mode::RepeatSingle, 0..=7) => 1u8 << channel,
+            (Mode::Sequence, _) | (Mode::RepeatSequence, _) => {
+                ((1u16 << (channel.min(7) + 1)) - 1) as u8
+            }
+            _ => 0,
+        };
+        self.inner.adc10ae0.write(|w| unsafe { w.bits(ae) });
+
+        self.inner.adc10ctl1.write(|w| unsafe {
+            w.inch()
+                .bits(channel)
+                .conseq()
+                .bits(mode as u8)
+                .adc10div()
+                .bits(self.div as u8)
+        });
+    }
+
+    // Starts the conversions and returns how many results the DTC will write, or returns 0
+    // without starting anything if that's none.
+    fn start_dtc(
+        &mut self,
+        channel: u8,
+        mode: Mode,
+        buf: *mut u16,
+        len: usize,
+        interrupt: bool,
+    ) -> usize {
+        let len = block_len(channel, mode, len);
+        if len == 0 {
+            return 0;
+        }
+
+        self.select(channel, mode);
+
+        // Multiple sample and conversion, so that sequences and repeats don't need a new
+        // ADC10SC for every conversion.
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.msc().bit(mode != Mode::Single).adc10ifg().clear_bit());
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.adc10ie().bit(interrupt));
+
+        // One-block, non-continuous transfer.
+        self.inner.adc10dtc0.write(|w| unsafe { w.bits(0) });
+        self.inner.adc10dtc1.write(|w| unsafe { w.bits(len as u8) });
+        // Writing ADC10SA arms the DTC.
+        self.inner.adc10sa.write(|w| unsafe { w.bits(buf as u16) });
+
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.enc().set_bit().adc10sc().set_bit());
+
+        len
+    }
+
+    fn stop(&mut self) {
+        // Repeat modes keep converting after the DTC is done. CONSEQx can only be changed with
+        // ENC clear, which lets the conversion or sequence in progress finish first.
+        self.inner
+            .adc10ctl0
+            .modify(|_, w| w.enc().clear_bit().adc10ie().clear_bit().adc10ifg().clear_bit());
+        while self.inner.adc10ctl1.read().adc10busy().bit_is_set() {}
+
+        self.inner
+            .adc10ctl1
+            .modify(|_, w| w.conseq().bits(Mode::Single as u8));
+        self.inner.adc10dtc1.write(|w| unsafe { w.bits(0) });
+    }
+
+    // Call from the `ADC10` interrupt handler.
+    pub fn on_interrupt(cs: mspint::CriticalSection) {
+        // SAFETY: Only the interrupt enable and flag are touched; the ADC10 is otherwise owned
+        // by the `Transfer` in progress.
+        let adc = unsafe { &*{{device}}::ADC10::ptr() };
+        adc.adc10ctl0
+            .modify(|_, w| w.adc10ie().clear_bit().adc10ifg().clear_bit());
+        DTC_DONE.borrow(cs).set(true);
+    }
+}
+
+// DTC transfer running in the background. The buffer is inaccessible until it completes.
+pub struct Transfer {
+    adc: Adc10,
+    buf: &'static mut [u16],
+    len: usize,
+}
+
+#[allow(unused)]
+impl Transfer {
+    pub fn is_done(&self) -> bool {
+        mspcs::with(|cs| DTC_DONE.borrow(cs).get())
+    }
+
+    // Number of results the transfer writes to the start of the buffer.
+    pub fn count(&self) -> usize {
+        self.len
+    }
+
+    // Block until the transfer completes, and give back the ADC, the buffer, and the number of
+    // results written to it.
+    pub fn wait(mut self) -> (Adc10, &'static mut [u16], usize) {
+        while !self.is_done() {}
+
+        if self.len != 0 {
+            self.adc.stop();
+        }
+        (self.adc, self.buf, self.len)
+    }
+}
@@ -0,0 +1,126 @@
+// Declarative sharing of resources between interrupt handlers ("tasks") and the idle loop, in
+// place of hand-written `Mutex<RefCell<Option<_>>>` statics:
+//
+//     app! {
+//         resources: {
+//             timer: TIMER0_A3,
+//             ticks: u16,
+//         },
+//         init: init,
+//         idle: idle,
+//         tasks: {
+//             TIMER0_A1: on_timer,
+//         },
+//     }
+//
+//     fn init(cs: CriticalSection) -> Resources { ... }
+//     fn idle(idle: Idle<Resources>) -> ! { ... }
+//     fn on_timer(res: &mut Resources, cs: CriticalSection) { ... }
+//
+// `app!` defines the `Resources` struct, stores what `init` returns before interrupts are
+// enabled, and defines the entry point, which runs `idle`. Each task is bound to its interrupt
+// vector with `wake_interrupt!`, and wakes idle if it sleeps.
+//
+// MSP430 interrupt handlers don't nest, so a task has exclusive access to all resources while
+// it runs, without locking. Idle can be interrupted at any time, so it has to `lock` them. A task
+// must not re-enable interrupts, so it can't be a `nested_interrupt!` handler. Those may still be
+// used alongside; their second half can't reach the resources, and tasks preempting it are fine.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+use msp430::{interrupt as mspint, critical_section as mspcs};
+
+use super::lpm::{self, LowPowerMode};
+
+// Storage for the resources, written once by `app!` before interrupts are enabled.
+pub struct AppResources<R> {
+    cell: UnsafeCell<MaybeUninit<R>>,
+}
+
+// SAFETY: Access is limited to tasks and `Idle::lock`, which never overlap.
+unsafe impl<R: Send> Sync for AppResources<R> {}
+
+#[allow(unused)]
+impl<R> AppResources<R> {
+    pub const fn uninit() -> Self {
+        AppResources {
+            cell: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    // SAFETY: Must be called exactly once, with interrupts disabled, before `as_ptr` is
+    // dereferenced.
+    pub unsafe fn init(&self, resources: R) {
+        (*self.cell.get()).write(resources);
+    }
+
+    pub fn as_ptr(&self) -> *mut R {
+        self.cell.get() as *mut R
+    }
+}
+
+// Idle's access to the resources.
+pub struct Idle<R: 'static> {
+    resources: &'static AppResources<R>,
+}
+
+#[allow(unused)]
+impl<R> Idle<R> {
+    // SAFETY: Only one may exist, created after the resources were initialized.
+    pub unsafe fn new(resources: &'static AppResources<R>) -> Self {
+        Idle { resources }
+    }
+
+    // Access the resources with interrupts disabled.
+    pub fn lock<T, F: FnOnce(&mut R, mspint::CriticalSection) -> T>(&mut self, f: F) -> T {
+        // SAFETY: No task runs during the critical section, and `&mut self` rules out a second
+        // lock inside `f`.
+        mspcs::with(|cs| f(unsafe { &mut *self.resources.as_ptr() }, cs))
+    }
+
+    // `lpm::sleep_unless`, deciding with access to the resources.
+    pub fn sleep_unless<F: FnOnce(&mut R) -> bool>(&mut self, mode: LowPowerMode, ready: F) {
+        // SAFETY: As for `lock`; `ready` runs with interrupts disabled.
+        lpm::sleep_unless(mode, |_| ready(unsafe { &mut *self.resources.as_ptr() }));
+    }
+}
+
+#[macro_export]
+macro_rules! app {
+    (
+        resources: { $($res:ident: $ty:ty),* $(,)? },
+        init: $init:path,
+        idle: $idle:path,
+        tasks: { $($vector:ident: $task:path),* $(,)? } $(,)?
+    ) => {
+        pub struct Resources {
+            $(pub $res: $ty,)*
+        }
+
+        static __APP_RESOURCES: $crate::hal::app::AppResources<Resources> =
+            $crate::hal::app::AppResources::uninit();
+
+        fn __app_init(cs: msp430::interrupt::CriticalSection) {
+            let resources: Resources = $init(cs);
+            // SAFETY: Interrupts are still disabled, and this is the only call.
+            unsafe { __APP_RESOURCES.init(resources) };
+        }
+
+        #[msp430_rt::entry(interrupt_enable(pre_interrupt = __app_init))]
+        fn __app_main() -> ! {
+            // SAFETY: The resources were initialized by `__app_init`, and this is the only Idle.
+            $idle(unsafe { $crate::hal::app::Idle::new(&__APP_RESOURCES) })
+        }
+
+        $(
+            $crate::wake_interrupt!($vector, |cs| {
+                // SAFETY: Initialized before interrupts were enabled. Tasks don't nest, and idle
+                // only accesses the resources with interrupts disabled.
+                let resources = unsafe { &mut *__APP_RESOURCES.as_ptr() };
+                $task(resources, cs);
+                true
+            });
+        )*
+    };
+}
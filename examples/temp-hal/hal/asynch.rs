@@ -0,0 +1,461 @@
+// Interrupt-driven async versions of the Timer, Serial and I2c drivers and port 1 interrupts,
+// for tasks run by `executor`. The interrupt handlers below only disable the interrupt that
+// fired and wake the waiting future, which does the actual work when polled. Bind them with
+// `wake_interrupt!`:
+//
+//     wake_interrupt!(TIMER0_A1, hal::asynch::timer_interrupt);
+//     wake_interrupt!(USCIAB0TX, hal::asynch::usci_tx_interrupt);
+//     wake_interrupt!(USCIAB0RX, hal::asynch::usci_rx_interrupt);
+//     wake_interrupt!(PORT1, hal::asynch::port1_interrupt);
+//
+// The UART and I2C share the USCI vectors, so both rely on the same two handlers. Dropping a
+// future in the middle of a transfer leaves the peripheral in the middle of it too.
+
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use embedded_hal::i2c::{self, blocking::Operation, NoAcknowledgeSource};
+use embedded_hal::serial;
+use msp430::{interrupt as mspint, critical_section as mspcs};
+
+use super::executor::WakerSlot;
+use super::gpio::{PinEvents, Port1Interrupts};
+use super::{I2c, Serial, Timer};
+
+// IE2 and IFG2 bits.
+const UCA0RX: u8 = 0x01;
+const UCA0TX: u8 = 0x02;
+const UCB0RX: u8 = 0x04;
+const UCB0TX: u8 = 0x08;
+
+static TIMER_WAKER: WakerSlot = WakerSlot::new();
+static TIMER_ELAPSED: mspint::Mutex<Cell<bool>> = mspint::Mutex::new(Cell::new(false));
+static SERIAL_TX_WAKER: WakerSlot = WakerSlot::new();
+static SERIAL_RX_WAKER: WakerSlot = WakerSlot::new();
+static I2C_WAKER: WakerSlot = WakerSlot::new();
+static PORT1_WAKER: WakerSlot = WakerSlot::new();
+static PORT1_EVENTS: mspint::Mutex<Cell<Option<PinEvents>>> = mspint::Mutex::new(Cell::new(None));
+
+fn sfr() -> &'static {{device}}::special_function::RegisterBlock {
+    // SAFETY: Only IE2 and IFG2 are accessed, and only the USCI bits, which the drivers here
+    // own. Read-modify-writes of IE2 happen in critical sections.
+    unsafe { &*{{device}}::SPECIAL_FUNCTION::ptr() }
+}
+
+fn flag_is_set(mask: u8) -> bool {
+    sfr().ifg2.read().bits() & mask != 0
+}
+
+// Register the waker, then enable the interrupt. If the flag was set in between, the interrupt
+// fires right away and wakes the future again.
+fn wait<T>(slot: &WakerSlot, ie2: u8, cx: &mut Context) -> Poll<T> {
+    mspcs::with(|cs| {
+        slot.register(cs, cx.waker());
+        sfr().ie2.modify(|r, w| unsafe { w.bits(r.bits() | ie2) });
+    });
+
+    Poll::Pending
+}
+
+pub struct AsyncTimer {
+    _timer: Timer,
+}
+
+#[allow(unused)]
+impl AsyncTimer {
+    // Takes over a started `Timer`. Its interrupt must be handled by `timer_interrupt` from now
+    // on.
+    pub fn new(timer: Timer) -> Self {
+        mspcs::with(|cs| TIMER_ELAPSED.borrow(cs).set(false));
+        AsyncTimer { _timer: timer }
+    }
+
+    // Completes at the end of the current period, like `Timer::wait`. A period that ended
+    // while nothing was waiting completes the next wait immediately.
+    pub fn wait(&mut self) -> TickFuture<'_> {
+        TickFuture { _timer: self }
+    }
+}
+
+pub struct TickFuture<'a> {
+    _timer: &'a mut AsyncTimer,
+}
+
+impl Future for TickFuture<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        mspcs::with(|cs| {
+            if TIMER_ELAPSED.borrow(cs).replace(false) {
+                Poll::Ready(())
+            } else {
+                TIMER_WAKER.register(cs, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+pub struct AsyncSerial {
+    serial: Serial,
+}
+
+#[allow(unused)]
+impl AsyncSerial {
+    // The USCI interrupts must be handled by `usci_tx_interrupt` and `usci_rx_interrupt`.
+    pub fn new(serial: Serial) -> Self {
+        AsyncSerial { serial }
+    }
+
+    pub fn write_all<'a>(&'a mut self, bytes: &'a [u8]) -> WriteFuture<'a> {
+        WriteFuture {
+            serial: self,
+            bytes,
+        }
+    }
+
+    pub fn read(&mut self) -> ReadFuture<'_> {
+        ReadFuture { serial: self }
+    }
+}
+
+pub struct WriteFuture<'a> {
+    serial: &'a mut AsyncSerial,
+    bytes: &'a [u8],
+}
+
+impl Future for WriteFuture<'_> {
+    type Output = Result<(), serial::ErrorKind>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        while let Some((&byte, rest)) = this.bytes.split_first() {
+            if !flag_is_set(UCA0TX) {
+                return wait(&SERIAL_TX_WAKER, UCA0TX, cx);
+            }
+
+            this.serial.serial.inner.uca0txbuf.write(|w| w.bits(byte));
+            this.bytes = rest;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct ReadFuture<'a> {
+    serial: &'a mut AsyncSerial,
+}
+
+impl Future for ReadFuture<'_> {
+    type Output = Result<u8, serial::ErrorKind>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if !flag_is_set(UCA0RX) {
+            return wait(&SERIAL_RX_WAKER, UCA0RX, cx);
+        }
+
+        let inner = &self.serial.serial.inner;
+        let stat = inner.uca0stat.read();
+        // Reading the byte also clears the error flags.
+        let byte = inner.uca0rxbuf.read().bits();
+
+        Poll::Ready(if stat.ucoe().bit_is_set() {
+            Err(serial::ErrorKind::Overrun)
+        } else if stat.ucfe().bit_is_set() {
+            Err(serial::ErrorKind::FrameFormat)
+        } else if stat.ucpe().bit_is_set() {
+            Err(serial::ErrorKind::Parity)
+        } else {
+            Ok(byte)
+        })
+    }
+}
+
+pub struct AsyncI2c {
+    i2c: I2c,
+}
+
+// The methods of embedded-hal's blocking `I2c`, as futures. embedded-hal-async 0.1.0-alpha.1 is
+// the release that pins the embedded-hal alpha the examples use, but it no longer compiles on
+// current nightly: its `SpiDevice` defaults rely on GAT and TAIT behaviour that has since
+// changed. So there is no trait to implement.
+#[allow(unused)]
+impl AsyncI2c {
+    // The USCI interrupts must be handled by `usci_tx_interrupt` and `usci_rx_interrupt`.
+    pub fn new(i2c: I2c) -> Self {
+        AsyncI2c { i2c }
+    }
+
+    pub fn read<'a>(&'a mut self, address: u8, read: &'a mut [u8]) -> Transfer<'a> {
+        Transfer::new(self, address, &[], read)
+    }
+
+    pub fn write<'a>(&'a mut self, address: u8, write: &'a [u8]) -> Transfer<'a> {
+        Transfer::new(self, address, write, &mut [])
+    }
+
+    pub fn write_read<'a>(
+        &'a mut self,
+        address: u8,
+        write: &'a [u8],
+        read: &'a mut [u8],
+    ) -> Transfer<'a> {
+        Transfer::new(self, address, write, read)
+    }
+
+    // Only a single write or read, or a write followed by a read, are supported.
+    pub async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), i2c::ErrorKind> {
+        match operations {
+            [] => Ok(()),
+            [Operation::Write(write)] => self.write(address, write).await,
+            [Operation::Read(read)] => self.read(address, read).await,
+            [Operation::Write(write), Operation::Read(read)] => {
+                self.write_read(address, write, read).await
+            }
+            // Anything else needs consecutive operations of the same kind joined without a
+            // repeated start, which `Transfer` doesn't do.
+            _ => Err(i2c::ErrorKind::Other),
+        }
+    }
+}
+
+impl i2c::ErrorType for AsyncI2c {
+    type Error = i2c::ErrorKind;
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Start,
+    Write(usize),
+    Read(usize),
+}
+
+// A write, a read, or a write followed by a read with a repeated start in between.
+pub struct Transfer<'a> {
+    i2c: &'a mut AsyncI2c,
+    address: u8,
+    write: &'a [u8],
+    read: &'a mut [u8],
+    state: State,
+    stopping: bool,
+}
+
+impl<'a> Transfer<'a> {
+    fn new(i2c: &'a mut AsyncI2c, address: u8, write: &'a [u8], read: &'a mut [u8]) -> Self {
+        Transfer {
+            i2c,
+            address,
+            write,
+            read,
+            state: State::Start,
+            stopping: false,
+        }
+    }
+
+    fn regs(&self) -> &{{device}}::USCI_B0_I2C_MODE {
+        &self.i2c.i2c.inner
+    }
+
+    fn start(&mut self, transmit: bool) -> Result<(), i2c::ErrorKind> {
+        let regs = self.regs();
+        regs.ucb0ctl1.modify(|_, w| w.uctr().bit(transmit));
+        regs.ucb0ctl1.modify(|_, w| w.uctxstt().set_bit()); // Generate (repeated) start condition.
+
+        if !transmit {
+            // For a single byte read, the stop condition must be requested as soon as the
+            // address has been sent, so wait for that here (about 100us at 100kHz) rather than
+            // risk the interrupt coming too late.
+            while regs.ucb0ctl1.read().uctxstt().bit_is_set() {}
+            self.check_nack(NoAcknowledgeSource::Address)?;
+        }
+
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.regs().ucb0ctl1.modify(|_, w| w.uctxstp().set_bit());
+        self.stopping = true;
+    }
+
+    fn check_nack(&mut self, source: NoAcknowledgeSource) -> Result<(), i2c::ErrorKind> {
+        if self.regs().ucb0stat.read().ucnackifg().bit_is_clear() {
+            return Ok(());
+        }
+
+        self.stop();
+        self.regs().ucb0stat.modify(|_, w| w.ucnackifg().clear_bit());
+        Err(i2c::ErrorKind::NoAcknowledge(source))
+    }
+
+    // Also listen for a NACK, which is signalled on the other vector.
+    fn wait(&self, ie2: u8, cx: &mut Context) -> Poll<Result<(), i2c::ErrorKind>> {
+        mspcs::with(|_| self.regs().ucb0i2cie.modify(|_, w| w.ucnackie().set_bit()));
+        wait(&I2C_WAKER, ie2, cx)
+    }
+}
+
+impl Future for Transfer<'_> {
+    type Output = Result<(), i2c::ErrorKind>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            match this.state {
+                State::Start => {
+                    let address = this.address;
+                    this.regs()
+                        .ucb0i2csa
+                        .write(|w| w.ucsa().bits(address.into()));
+
+                    if this.write.is_empty() && !this.read.is_empty() {
+                        this.start(false)?;
+                        this.state = State::Read(0);
+                    } else {
+                        this.start(true)?;
+                        this.state = State::Write(0);
+                    }
+                }
+                State::Write(i) => {
+                    this.check_nack(NoAcknowledgeSource::Unknown)?;
+                    if !flag_is_set(UCB0TX) {
+                        return this.wait(UCB0TX, cx);
+                    }
+
+                    if let Some(&byte) = this.write.get(i) {
+                        this.regs().ucb0txbuf.write(|w| w.bits(byte));
+                        this.state = State::Write(i + 1);
+                    } else if this.read.is_empty() {
+                        this.stop();
+                        return Poll::Ready(Ok(()));
+                    } else {
+                        this.start(false)?;
+                        this.state = State::Read(0);
+                    }
+                }
+                State::Read(i) => {
+                    // Request the stop condition while the last byte is being received.
+                    if i + 1 == this.read.len() && !this.stopping {
+                        this.stop();
+                    }
+
+                    if !flag_is_set(UCB0RX) {
+                        return this.wait(UCB0RX, cx);
+                    }
+
+                    this.read[i] = this.regs().ucb0rxbuf.read().ucb0rxbuf().bits();
+                    if i + 1 == this.read.len() {
+                        return Poll::Ready(Ok(()));
+                    }
+
+                    this.state = State::Read(i + 1);
+                }
+            }
+        }
+    }
+}
+
+pub struct AsyncPort1 {
+    _int: Port1Interrupts,
+}
+
+#[allow(unused)]
+impl AsyncPort1 {
+    // Takes over port 1's interrupts, which must be handled by `port1_interrupt` from now on.
+    // Enable them on the pins as usual.
+    pub fn new(int: Port1Interrupts) -> Self {
+        AsyncPort1 { _int: int }
+    }
+
+    // Completes with the pins whose interrupt fired since the last wait.
+    pub fn wait(&mut self) -> PortFuture<'_> {
+        PortFuture { _port: self }
+    }
+}
+
+pub struct PortFuture<'a> {
+    _port: &'a mut AsyncPort1,
+}
+
+impl Future for PortFuture<'_> {
+    type Output = PinEvents;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<PinEvents> {
+        mspcs::with(|cs| match PORT1_EVENTS.borrow(cs).take() {
+            Some(events) => Poll::Ready(events),
+            None => {
+                PORT1_WAKER.register(cs, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+// Handler for TIMER0_A1 while an `AsyncTimer` is in use.
+#[allow(unused)]
+pub fn timer_interrupt(cs: mspint::CriticalSection) -> bool {
+    // SAFETY: `AsyncTimer` leaves TACCTL1 to this handler once the timer is started.
+    let timer = unsafe { &*{{device}}::TIMER0_A3::ptr() };
+    timer.tacctl1.modify(|_, w| w.ccifg().clear_bit());
+
+    TIMER_ELAPSED.borrow(cs).set(true);
+    TIMER_WAKER.wake(cs)
+}
+
+// Handler for USCIAB0TX: UART transmit, and I2C transmit and receive.
+#[allow(unused)]
+pub fn usci_tx_interrupt(cs: mspint::CriticalSection) -> bool {
+    let sfr = sfr();
+    let pending = sfr.ie2.read().bits() & sfr.ifg2.read().bits() & (UCA0TX | UCB0TX | UCB0RX);
+    sfr.ie2.modify(|r, w| unsafe { w.bits(r.bits() & !pending) });
+
+    let mut woken = false;
+    if pending & UCA0TX != 0 {
+        woken |= SERIAL_TX_WAKER.wake(cs);
+    }
+    if pending & (UCB0TX | UCB0RX) != 0 {
+        woken |= I2C_WAKER.wake(cs);
+    }
+
+    woken
+}
+
+// Handler for USCIAB0RX: UART receive, and I2C NACKs.
+#[allow(unused)]
+pub fn usci_rx_interrupt(cs: mspint::CriticalSection) -> bool {
+    let sfr = sfr();
+    let mut woken = false;
+
+    if sfr.ie2.read().bits() & sfr.ifg2.read().bits() & UCA0RX != 0 {
+        sfr.ie2.modify(|r, w| unsafe { w.bits(r.bits() & !UCA0RX) });
+        woken |= SERIAL_RX_WAKER.wake(cs);
+    }
+
+    // SAFETY: `AsyncI2c` leaves UCB0I2CIE to this handler and `Transfer::wait`, which only
+    // modifies it in a critical section.
+    let i2c = unsafe { &*{{device}}::USCI_B0_I2C_MODE::ptr() };
+    if i2c.ucb0i2cie.read().ucnackie().bit_is_set() && i2c.ucb0stat.read().ucnackifg().bit_is_set()
+    {
+        i2c.ucb0i2cie.modify(|_, w| w.ucnackie().clear_bit());
+        woken |= I2C_WAKER.wake(cs);
+    }
+
+    woken
+}
+
+// Handler for PORT1 while an `AsyncPort1` is in use.
+#[allow(unused)]
+pub fn port1_interrupt(cs: mspint::CriticalSection) -> bool {
+    let pending = Port1Interrupts::steal().take_pending();
+
+    let events = PORT1_EVENTS.borrow(cs);
+    events.set(Some(events.get().map_or(pending, |e| e.union(pending))));
+    PORT1_WAKER.wake(cs)
+}
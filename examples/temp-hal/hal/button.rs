@@ -0,0 +1,176 @@
+// Debounced push button, driven by the pin's edge interrupt and a periodic tick (for instance
+// the watchdog in interval mode). The edge interrupt only wakes the state machine up; the pin
+// is then sampled on every tick until it's been stable and idle, so contact bounce never
+// reaches the application. Events are pushed to an SPSC queue, which the main loop pops without
+// disabling interrupts:
+//
+//     static EVENTS: EventQueue = EventQueue::new();
+//
+//     // SAFETY: The only call, so there is one producer and one consumer.
+//     let (events, mut rx) = unsafe { EVENTS.split_unchecked() };
+//     let button = Button::new(pin, true, config, events);
+//
+//     while let Some(ev) = rx.pop() { ... }
+
+use embedded_hal::digital::blocking::InputPin;
+
+use super::spsc::{Consumer, Producer, Queue};
+
+const QUEUE_LEN: usize = 8;
+
+pub type EventQueue = Queue<ButtonEvent, QUEUE_LEN>;
+pub type EventProducer = Producer<'static, ButtonEvent, QUEUE_LEN>;
+pub type EventConsumer = Consumer<'static, ButtonEvent, QUEUE_LEN>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Press,
+    Release,
+    // Short press and release, not followed by another press within the double-click window.
+    Click,
+    DoubleClick,
+    // Emitted once while still held; no click follows on release.
+    LongPress,
+}
+
+// Durations in ticks.
+#[derive(Clone, Copy, Debug)]
+pub struct ButtonConfig {
+    pub debounce: u16,
+    pub double_click: u16,
+    pub long_press: u16,
+}
+
+impl ButtonConfig {
+    // 30 ms debounce, 300 ms double-click window, 800 ms long press.
+    pub fn from_tick_ms(tick_ms: u16) -> Self {
+        let ticks = |ms: u16| (ms / tick_ms.max(1)).max(1);
+
+        ButtonConfig {
+            debounce: ticks(30),
+            double_click: ticks(300),
+            long_press: ticks(800),
+        }
+    }
+}
+
+pub struct Button<P> {
+    pin: P,
+    active_low: bool,
+    config: ButtonConfig,
+    // Debounced state.
+    pressed: bool,
+    // Consecutive ticks the pin has disagreed with `pressed`.
+    settle: u16,
+    // Ticks since the last debounced transition, saturating.
+    held: u16,
+    // An edge was seen and ticks are sampling the pin.
+    active: bool,
+    long_sent: bool,
+    // A click waiting to find out if it's the first half of a double click.
+    click_pending: bool,
+    events: EventProducer,
+}
+
+impl<P: InputPin> Button<P> {
+    // `active_low` for a button that shorts the pin to ground with a pullup enabled, like S2
+    // on the MSP-EXP430G2.
+    pub fn new(pin: P, active_low: bool, config: ButtonConfig, events: EventProducer) -> Self {
+        Button {
+            pin,
+            active_low,
+            config,
+            pressed: false,
+            settle: 0,
+            held: 0,
+            active: false,
+            long_sent: false,
+            click_pending: false,
+            events,
+        }
+    }
+
+    // Call from the port interrupt handler when this pin's flag was set.
+    pub fn on_edge(&mut self) {
+        self.active = true;
+    }
+
+    // Call from the periodic tick interrupt.
+    pub fn tick(&mut self) {
+        if !self.active {
+            return;
+        }
+
+        if self.sample() != self.pressed {
+            self.settle += 1;
+
+            if self.settle >= self.config.debounce {
+                self.settle = 0;
+                self.pressed = !self.pressed;
+                self.transition();
+            }
+        } else {
+            self.settle = 0;
+        }
+
+        self.held = self.held.saturating_add(1);
+
+        if self.pressed {
+            if !self.long_sent && self.held >= self.config.long_press {
+                if self.click_pending {
+                    self.click_pending = false;
+                    self.push(ButtonEvent::Click);
+                }
+
+                self.long_sent = true;
+                self.push(ButtonEvent::LongPress);
+            }
+        } else {
+            if self.click_pending && self.held >= self.config.double_click {
+                self.click_pending = false;
+                self.push(ButtonEvent::Click);
+            }
+
+            // Released, stable, and nothing left to time; wait for the next edge.
+            if !self.click_pending && self.settle == 0 {
+                self.active = false;
+            }
+        }
+    }
+
+    // Whether events are waiting for the main loop, e.g. to decide if the tick should wake it.
+    pub fn has_events(&self) -> bool {
+        self.events.len() > 0
+    }
+
+    // New events are dropped if the main loop falls behind.
+    fn push(&mut self, ev: ButtonEvent) {
+        let _ = self.events.push(ev);
+    }
+
+    fn sample(&self) -> bool {
+        // Infallible for the GPIO in this HAL; treat errors as "not pressed".
+        let high = self.pin.is_high().unwrap_or(self.active_low);
+        high != self.active_low
+    }
+
+    fn transition(&mut self) {
+        self.held = 0;
+
+        if self.pressed {
+            self.long_sent = false;
+            self.push(ButtonEvent::Press);
+        } else {
+            self.push(ButtonEvent::Release);
+
+            if !self.long_sent {
+                if self.click_pending {
+                    self.click_pending = false;
+                    self.push(ButtonEvent::DoubleClick);
+                } else {
+                    self.click_pending = true;
+                }
+            }
+        }
+    }
+}
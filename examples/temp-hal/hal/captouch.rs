@@ -0,0 +1,294 @@
+// Capacitive touch sensing using the PinOsc feature of the G2xx3 parts, along the lines of TI's
+// Capacitive Touch library ("RO_PINOSC_TA0_WDTp"). Setting PxSEL2 alone turns a pin into a
+// relaxation oscillator whose output drives TIMER0_A3's INCLK. TIMER0_A3 counts oscillations for
+// one watchdog interval; a finger adds capacitance, which lowers the frequency and the count.
+//
+// Each electrode keeps a baseline count which follows slow environmental drift while nothing is
+// touched. A touch is the count falling below the baseline by more than a threshold.
+
+use super::gpio::OscPin;
+use super::{WatchdogDivider, WdtIfg};
+
+// Measurements averaged for the initial baseline.
+const CALIBRATION_SAMPLES: u32 = 8;
+// Baselines are kept with 4 fractional bits so that slow tracking doesn't round to nothing.
+const BASELINE_SHIFT: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchEvent {
+    Touch,
+    Release,
+}
+
+pub struct CapTouch {
+    timer: {{device}}::TIMER0_A3,
+    wdt: {{device}}::WATCHDOG_TIMER,
+    wdtifg: WdtIfg,
+    gate: WatchdogDivider,
+}
+
+#[allow(unused)]
+impl CapTouch {
+    // The gate time is `gate` SMCLK cycles; longer gates are more sensitive but slower. The
+    // watchdog is held between measurements.
+    pub fn new(
+        timer: {{device}}::TIMER0_A3,
+        wdt: {{device}}::WATCHDOG_TIMER,
+        wdtifg: WdtIfg,
+        gate: WatchdogDivider,
+    ) -> Self {
+        wdt.wdtctl
+            .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+        CapTouch {
+            timer,
+            wdt,
+            wdtifg,
+            gate,
+        }
+    }
+
+    // Raw oscillator count for one gate interval.
+    pub fn measure(&mut self, pin: &OscPin) -> u16 {
+        pin.set_osc(true);
+
+        self.timer
+            .tactl
+            .write(|w| w.tassel().tassel_3().mc().mc_2().taclr().set_bit());
+        // Software capture into TACCR1: switching CCIS from GND to VCC is a rising edge.
+        self.timer
+            .tacctl1
+            .write(|w| w.cm().cm_3().ccis().ccis_2().cap().set_bit());
+
+        self.wdt.wdtctl.write(|w| {
+            w.wdtpw()
+                .password()
+                .wdttmsel()
+                .set_bit()
+                .wdtcntcl()
+                .set_bit()
+                .wdtis()
+                .bits(self.gate as u8)
+        });
+        self.wdtifg.clear();
+        while !self.wdtifg.bit_is_set() {}
+
+        self.timer.tacctl1.modify(|_, w| w.ccis().ccis_3());
+        let count = self.timer.taccr1.read().bits();
+
+        self.wdt
+            .wdtctl
+            .write(|w| w.wdtpw().password().wdthold().set_bit());
+        self.timer.tactl.write(|w| w.mc().mc_0());
+        pin.set_osc(false);
+
+        count
+    }
+}
+
+pub struct Element {
+    pin: OscPin,
+    baseline: u32,
+}
+
+impl Element {
+    pub fn new(pin: OscPin) -> Self {
+        Element { pin, baseline: 0 }
+    }
+
+    fn calibrate(&mut self, ct: &mut CapTouch) {
+        let mut sum = 0u32;
+        for _ in 0..CALIBRATION_SAMPLES {
+            sum += u32::from(ct.measure(&self.pin));
+        }
+
+        self.baseline = (sum << BASELINE_SHIFT) / CALIBRATION_SAMPLES;
+    }
+
+    // Measure, returning how far the count is below the baseline.
+    fn scan(&mut self, ct: &mut CapTouch, touched: bool) -> u16 {
+        let count = u32::from(ct.measure(&self.pin)) << BASELINE_SHIFT;
+
+        let delta = self.baseline.saturating_sub(count) >> BASELINE_SHIFT;
+
+        // A count above the baseline means less capacitance than when calibrated (e.g. a hand
+        // was nearby at startup); follow it quickly. Drift downwards is followed slowly, and
+        // not at all while touched, so that a touch is never absorbed into the baseline.
+        if count > self.baseline {
+            self.baseline += (count - self.baseline) / 4;
+        } else if !touched {
+            self.baseline -= (self.baseline - count) / 64;
+        }
+
+        delta as u16
+    }
+}
+
+pub struct TouchButton {
+    element: Element,
+    threshold: u16,
+    touched: bool,
+}
+
+#[allow(unused)]
+impl TouchButton {
+    pub fn new(pin: OscPin, threshold: u16) -> Self {
+        TouchButton {
+            element: Element::new(pin),
+            threshold,
+            touched: false,
+        }
+    }
+
+    // Call once at startup, without a finger on the electrode.
+    pub fn calibrate(&mut self, ct: &mut CapTouch) {
+        self.element.calibrate(ct);
+    }
+
+    pub fn scan(&mut self, ct: &mut CapTouch) -> Option<TouchEvent> {
+        let delta = self.element.scan(ct, self.touched);
+        update_touched(&mut self.touched, delta, self.threshold)
+    }
+
+    pub fn is_touched(&self) -> bool {
+        self.touched
+    }
+}
+
+// Releases at half the touch threshold, so a finger resting right at the threshold doesn't
+// produce a stream of events.
+fn update_touched(touched: &mut bool, delta: u16, threshold: u16) -> Option<TouchEvent> {
+    if !*touched && delta >= threshold {
+        *touched = true;
+        Some(TouchEvent::Touch)
+    } else if *touched && delta < threshold / 2 {
+        *touched = false;
+        Some(TouchEvent::Release)
+    } else {
+        None
+    }
+}
+
+struct ElementGroup<const N: usize> {
+    elements: [Element; N],
+    deltas: [u16; N],
+    threshold: u16,
+    touched: bool,
+}
+
+impl<const N: usize> ElementGroup<N> {
+    fn new(pins: [OscPin; N], threshold: u16) -> Self {
+        ElementGroup {
+            elements: pins.map(Element::new),
+            deltas: [0; N],
+            threshold,
+            touched: false,
+        }
+    }
+
+    fn calibrate(&mut self, ct: &mut CapTouch) {
+        for el in self.elements.iter_mut() {
+            el.calibrate(ct);
+        }
+    }
+
+    fn scan(&mut self, ct: &mut CapTouch) -> Option<TouchEvent> {
+        for (el, delta) in self.elements.iter_mut().zip(self.deltas.iter_mut()) {
+            *delta = el.scan(ct, self.touched);
+        }
+
+        let max = self.deltas.iter().copied().max().unwrap_or(0);
+        update_touched(&mut self.touched, max, self.threshold)
+    }
+}
+
+// Linear slider; electrode 0 is position 0, electrode N-1 is position `resolution`.
+pub struct Slider<const N: usize> {
+    group: ElementGroup<N>,
+    resolution: u16,
+}
+
+#[allow(unused)]
+impl<const N: usize> Slider<N> {
+    pub fn new(pins: [OscPin; N], threshold: u16, resolution: u16) -> Self {
+        Slider {
+            group: ElementGroup::new(pins, threshold),
+            resolution,
+        }
+    }
+
+    pub fn calibrate(&mut self, ct: &mut CapTouch) {
+        self.group.calibrate(ct);
+    }
+
+    pub fn scan(&mut self, ct: &mut CapTouch) -> Option<TouchEvent> {
+        self.group.scan(ct)
+    }
+
+    // Centroid of the electrode deltas, while touched.
+    pub fn position(&self) -> Option<u16> {
+        if !self.group.touched || N < 2 {
+            return None;
+        }
+
+        let (sum, weighted) = self
+            .group
+            .deltas
+            .iter()
+            .enumerate()
+            .fold((0u32, 0u32), |(sum, weighted), (i, &d)| {
+                (sum + u32::from(d), weighted + u32::from(d) * i as u32)
+            });
+
+        if sum == 0 {
+            return None;
+        }
+
+        Some((weighted * u32::from(self.resolution) / ((N as u32 - 1) * sum)) as u16)
+    }
+}
+
+// Circular slider; electrode i is centered at position i * resolution / N, and positions wrap
+// around at `resolution`.
+pub struct Wheel<const N: usize> {
+    group: ElementGroup<N>,
+    resolution: u16,
+}
+
+#[allow(unused)]
+impl<const N: usize> Wheel<N> {
+    pub fn new(pins: [OscPin; N], threshold: u16, resolution: u16) -> Self {
+        Wheel {
+            group: ElementGroup::new(pins, threshold),
+            resolution,
+        }
+    }
+
+    pub fn calibrate(&mut self, ct: &mut CapTouch) {
+        self.group.calibrate(ct);
+    }
+
+    pub fn scan(&mut self, ct: &mut CapTouch) -> Option<TouchEvent> {
+        self.group.scan(ct)
+    }
+
+    // Interpolated between the strongest electrode and its two neighbours, while touched.
+    pub fn position(&self) -> Option<u16> {
+        if !self.group.touched || N < 3 {
+            return None;
+        }
+
+        let deltas = &self.group.deltas;
+        let (k, &d_k) = deltas.iter().enumerate().max_by_key(|&(_, d)| *d)?;
+        let d_prev = i32::from(deltas[(k + N - 1) % N]);
+        let d_next = i32::from(deltas[(k + 1) % N]);
+        let d_k = i32::from(d_k);
+
+        let res = i32::from(self.resolution);
+        let segment = res / N as i32;
+        let offset = (d_next - d_prev) * segment / (d_prev + d_k + d_next).max(1);
+        let pos = (k as i32 * segment + offset).rem_euclid(res);
+
+        Some(pos as u16)
+    }
+}
@@ -0,0 +1,345 @@
+use core::arch::asm;
+
+use super::tlv::{self, DcoFrequency};
+
+// Typical VLO frequency from the datasheet. The actual frequency can be anywhere between 4 and
+// 20 kHz depending on the part, voltage and temperature.
+pub const VLO_NOMINAL_HZ: u32 = 12_000;
+// Watch crystal, as shipped (unsoldered) with the MSP-EXP430G2.
+pub const LFXT1_WATCH_HZ: u32 = 32_768;
+// DCO after reset (RSELx = 7, DCOx = 3, MODx = 0).
+pub const DCO_DEFAULT_HZ: u32 = 1_100_000;
+
+// ACLK periods to average over when measuring the VLO.
+const VLO_MEASURE_PERIODS: u16 = 8;
+
+// How long to wait for the crystal before giving up on it. Crystals usually take a few hundred
+// milliseconds to start.
+const LFXT1_DEFAULT_TIMEOUT_MS: u16 = 1000;
+// MCLK cycles per iteration of `delay_loops`: one for `dec`, two for `jnz`.
+const DELAY_LOOP_CYCLES: u32 = 3;
+
+// Effective load capacitance presented to the crystal on XIN/XOUT (XCAPx bits).
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum Lfxt1Cap {
+    Cap1pF = 0,
+    Cap6pF = 1,
+    Cap10pF = 2,
+    Cap12_5pF = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AclkSource {
+    Vlo,
+    Lfxt1,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum AclkDivider {
+    By1 = 0,
+    By2 = 1,
+    By4 = 2,
+    By8 = 3,
+}
+
+impl AclkDivider {
+    fn divisor(self) -> u32 {
+        1 << (self as u8)
+    }
+}
+
+// Builder for the Basic Clock Module+. Nothing is written to the hardware until `freeze`.
+pub struct ClockConfig {
+    inner: {{device}}::SYSTEM_CLOCK,
+    aclk: AclkSource,
+    cap: Lfxt1Cap,
+    aclk_div: AclkDivider,
+    lfxt1_timeout_ms: u16,
+    dco: Option<DcoFrequency>,
+}
+
+impl ClockConfig {
+    pub fn new(inner: {{device}}::SYSTEM_CLOCK) -> Self {
+        ClockConfig {
+            inner,
+            aclk: AclkSource::Vlo,
+            cap: Lfxt1Cap::Cap6pF,
+            aclk_div: AclkDivider::By1,
+            lfxt1_timeout_ms: LFXT1_DEFAULT_TIMEOUT_MS,
+            dco: None,
+        }
+    }
+
+    // Load the factory DCO calibration for `freq` from info segment A. If segment A has been
+    // erased, the DCO is left at its reset frequency. 16 MHz requires Vcc >= 3.3V.
+    #[allow(unused)]
+    pub fn dco_calibrated(mut self, freq: DcoFrequency) -> Self {
+        self.dco = Some(freq);
+        self
+    }
+
+    #[allow(unused)]
+    pub fn aclk_vlo(mut self) -> Self {
+        self.aclk = AclkSource::Vlo;
+        self
+    }
+
+    // Source ACLK from a 32.768 kHz watch crystal on XIN/XOUT. If the crystal doesn't start
+    // within the timeout, `freeze` falls back to the VLO and reports the fault in `Clocks`.
+    #[allow(unused)]
+    pub fn aclk_lfxt1(mut self, cap: Lfxt1Cap) -> Self {
+        self.aclk = AclkSource::Lfxt1;
+        self.cap = cap;
+        self
+    }
+
+    pub fn aclk_divider(mut self, div: AclkDivider) -> Self {
+        self.aclk_div = div;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn lfxt1_timeout_ms(mut self, ms: u16) -> Self {
+        self.lfxt1_timeout_ms = ms;
+        self
+    }
+
+    pub fn freeze(self, osc_fault: &mut OscFault) -> Clocks {
+        let mut aclk_source = self.aclk;
+        let mut lfxt1_fault = false;
+        let mut dco_hz = DCO_DEFAULT_HZ;
+
+        let dco_cal = self.dco.and_then(|f| tlv::dco_calibration(f).map(|c| (f, c)));
+        if let Some((freq, cal)) = dco_cal {
+            // Select the lowest DCOx/MODx first so that the new RSELx can't momentarily run
+            // the DCO above the part's rated frequency.
+            self.inner.dcoctl.write(|w| w.bits(0));
+            self.inner.bcsctl1.write(|w| unsafe { w.bits(cal.bcsctl1) });
+            self.inner.dcoctl.write(|w| w.bits(cal.dcoctl));
+            dco_hz = freq.hz();
+        }
+
+        self.inner
+            .bcsctl1
+            .modify(|_, w| w.xts().clear_bit().diva().bits(self.aclk_div as u8));
+
+        match self.aclk {
+            AclkSource::Vlo => {
+                self.inner.bcsctl3.modify(|_, w| w.lfxt1s().lfxt1s_2());
+            }
+            AclkSource::Lfxt1 => {
+                self.inner
+                    .bcsctl3
+                    .modify(|_, w| w.lfxt1s().lfxt1s_0().xcap().bits(self.cap as u8));
+
+                if osc_fault.wait_for_lfxt1(self.lfxt1_timeout_ms, dco_hz) {
+                    // OFIE is cleared by hardware when the NMI is taken, so this only catches
+                    // the first fault after startup. See `OscFault::handle_nmi`.
+                    osc_fault.enable_interrupt();
+                } else {
+                    self.inner.bcsctl3.modify(|_, w| w.lfxt1s().lfxt1s_2());
+                    osc_fault.clear();
+                    aclk_source = AclkSource::Vlo;
+                    lfxt1_fault = true;
+                }
+            }
+        }
+
+        let aclk_undivided = match aclk_source {
+            AclkSource::Vlo => VLO_NOMINAL_HZ,
+            AclkSource::Lfxt1 => LFXT1_WATCH_HZ,
+        };
+
+        Clocks {
+            mclk: dco_hz,
+            smclk: dco_hz,
+            aclk: aclk_undivided / self.aclk_div.divisor(),
+            aclk_source,
+            lfxt1_fault,
+        }
+    }
+}
+
+// Frozen clock frequencies, in Hz.
+#[derive(Clone, Copy, Debug)]
+pub struct Clocks {
+    mclk: u32,
+    smclk: u32,
+    aclk: u32,
+    aclk_source: AclkSource,
+    lfxt1_fault: bool,
+}
+
+#[allow(unused)]
+impl Clocks {
+    pub fn mclk(&self) -> u32 {
+        self.mclk
+    }
+
+    pub fn smclk(&self) -> u32 {
+        self.smclk
+    }
+
+    pub fn aclk(&self) -> u32 {
+        self.aclk
+    }
+
+    pub fn aclk_source(&self) -> AclkSource {
+        self.aclk_source
+    }
+
+    // True if the crystal was requested but didn't start, and ACLK is running from the VLO
+    // instead.
+    pub fn lfxt1_fault(&self) -> bool {
+        self.lfxt1_fault
+    }
+
+    // Measure the actual (divided) VLO frequency against SMCLK and use it as the ACLK frequency
+    // from now on. SMCLK should come from a calibrated DCO (see `ClockConfig::dco_calibrated`),
+    // otherwise the result is only as good as the uncalibrated DCO. Does nothing if ACLK is
+    // sourced from the crystal.
+    //
+    // TIMER0_A3 is borrowed for the measurement and left stopped afterwards.
+    pub fn calibrate_vlo(&mut self, timer: &{{device}}::TIMER0_A3) -> u32 {
+        if self.aclk_source == AclkSource::Vlo {
+            let counts = measure_aclk(timer, VLO_MEASURE_PERIODS);
+            self.aclk = self.smclk * u32::from(VLO_MEASURE_PERIODS) / counts;
+        }
+
+        self.aclk
+    }
+
+    pub(super) fn set_dco(&mut self, hz: u32) {
+        self.mclk = hz;
+        self.smclk = hz;
+    }
+}
+
+// Count SMCLK cycles over `periods` rising edges of ACLK, by capturing ACLK (CCI0B) with
+// TIMER0_A3 running from SMCLK in continuous mode.
+pub(super) fn measure_aclk(timer: &{{device}}::TIMER0_A3, periods: u16) -> u32 {
+    timer
+        .tactl
+        .write(|w| w.tassel().tassel_2().mc().mc_2().taclr().set_bit());
+    timer.tacctl0.write(|w| {
+        w.cm()
+            .cm_1()
+            .ccis()
+            .ccis_1()
+            .scs()
+            .set_bit()
+            .cap()
+            .set_bit()
+    });
+
+    // The first capture only serves as a reference point.
+    let mut last = wait_capture(timer);
+    let mut total = 0u32;
+
+    for _ in 0..periods {
+        let now = wait_capture(timer);
+        total += u32::from(now.wrapping_sub(last));
+        last = now;
+    }
+
+    timer.tacctl0.write(|w| unsafe { w.bits(0) });
+    timer.tactl.write(|w| w.mc().mc_0().taclr().set_bit());
+
+    total
+}
+
+fn wait_capture(timer: &{{device}}::TIMER0_A3) -> u16 {
+    while timer.tacctl0.read().ccifg().bit_is_clear() {}
+    timer.tacctl0.modify(|_, w| w.ccifg().clear_bit());
+    timer.taccr0.read().bits()
+}
+
+// Oscillator fault flag and interrupt enable (IFG1.OFIFG/IE1.OFIE). Obtained from `SfrIfg`.
+pub struct OscFault {
+    sfr: {{device}}::SPECIAL_FUNCTION,
+    clock: {{device}}::SYSTEM_CLOCK,
+}
+
+impl OscFault {
+    pub(super) fn new(sfr: {{device}}::SPECIAL_FUNCTION, clock: {{device}}::SYSTEM_CLOCK) -> Self {
+        OscFault { sfr, clock }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.sfr.ifg1.read().ofifg().bit_is_set()
+    }
+
+    fn clear(&mut self) {
+        self.sfr.ifg1.modify(|_, w| w.ofifg().clear_bit());
+    }
+
+    fn enable_interrupt(&mut self) {
+        self.sfr.ie1.modify(|_, w| w.ofie().set_bit());
+    }
+
+    // OFIFG is set on reset and stays set while LFXT1OF indicates a fault. Keep clearing it,
+    // about once a millisecond of MCLK at `mclk_hz`, until it stays clear.
+    fn wait_for_lfxt1(&mut self, timeout_ms: u16, mclk_hz: u32) -> bool {
+        let loops = (mclk_hz / 1000 / DELAY_LOOP_CYCLES) as u16;
+
+        for _ in 0..timeout_ms {
+            self.clear();
+            delay_loops(loops);
+
+            if !self.is_pending() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Call from the `NMI` handler. If the crystal stopped, ACLK is switched to the VLO and
+    // `true` is returned so the application can adjust anything timed from ACLK.
+    #[allow(unused)]
+    pub fn handle_nmi(&mut self) -> bool {
+        if !self.is_pending() {
+            return false;
+        }
+
+        if self.clock.bcsctl3.read().lfxt1of().bit_is_set() {
+            self.clock.bcsctl3.modify(|_, w| w.lfxt1s().lfxt1s_2());
+            self.clear();
+            true
+        } else {
+            // Transient fault; crystal recovered on its own. Re-arm the NMI.
+            self.clear();
+            self.enable_interrupt();
+            false
+        }
+    }
+}
+
+// Iterations of `delay_loops` that take at least `us` microseconds of MCLK, for waits of up to
+// a few milliseconds.
+pub(super) fn delay_loops_for_us(clocks: &Clocks, us: u32) -> u16 {
+    let cycles = (clocks.mclk() / 1000 * us).div_ceil(1000);
+    cycles.div_ceil(DELAY_LOOP_CYCLES).min(u32::from(u16::MAX)) as u16
+}
+
+// Busy-wait for `n * DELAY_LOOP_CYCLES` cycles, whatever the optimization level.
+pub(super) fn delay_loops(n: u16) {
+    if n == 0 {
+        return;
+    }
+
+    // SAFETY: Only counts down a scratch register.
+    unsafe {
+        asm!(
+            "1:",
+            "dec {0}",
+            "jnz 1b",
+            inout(reg) n => _,
+            options(nomem, nostack),
+        )
+    };
+}
@@ -0,0 +1,261 @@
+// Comparator_A+ driver.
+//
+// The + terminal can be connected to CA0 through CA2, and the - terminal to CA1 through CA7,
+// where CAx is P1.x. Either terminal can instead be connected to an internal reference: 0.25 Vcc,
+// 0.5 Vcc, or the voltage across a diode (~0.55V). Pins connected to the comparator have their
+// digital input buffer disabled (CAPD), which avoids shoot-through current when the input sits
+// between logic levels.
+//
+// CAOUT is internally connected to TIMER0_A3 capture input CCI1B. Capturing the timer on CAOUT
+// edges gives cheap slope ADCs (time for an RC to charge past a reference) and zero-crossing
+// detection without an interrupt latency error.
+
+use msp430::interrupt as mspint;
+
+use super::gpio::{Edge, P1_0, P1_1, P1_2, P1_3, P1_4, P1_5, P1_6, P1_7};
+
+// P2CA4 and P2CA0, for the + terminal.
+const PLUS_MASK: u8 = 0b0100_0100;
+// P2CA3 through P2CA1, for the - terminal.
+const MINUS_MASK: u8 = 0b0011_1000;
+
+pub trait PlusInput {
+    // CAx
+    const CA: u8;
+    // P2CA4 and P2CA0 in CACTL2.
+    const P2CA: u8;
+}
+
+pub trait MinusInput {
+    // CAx
+    const CA: u8;
+    // P2CA3 through P2CA1 in CACTL2.
+    const P2CA: u8;
+}
+
+macro_rules! ca_pins {
+    ($Trait:ident: $($PXi:ident: ($ca:expr, $p2ca:expr),)+) => {
+        $(
+            impl<MODE> $Trait for $PXi<MODE> {
+                const CA: u8 = $ca;
+                const P2CA: u8 = $p2ca;
+            }
+        )+
+    };
+}
+
+ca_pins!(
+    PlusInput:
+    P1_0: (0, 0b0000_0100),
+    P1_1: (1, 0b0100_0000),
+    P1_2: (2, 0b0100_0100),
+);
+
+ca_pins!(
+    MinusInput:
+    P1_1: (1, 0b0000_1000),
+    P1_2: (2, 0b0001_0000),
+    P1_3: (3, 0b0001_1000),
+    P1_4: (4, 0b0010_0000),
+    P1_5: (5, 0b0010_1000),
+    P1_6: (6, 0b0011_0000),
+    P1_7: (7, 0b0011_1000),
+);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(unused)]
+pub enum Terminal {
+    Plus,
+    Minus,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(unused)]
+pub enum InternalRef {
+    QuarterVcc,
+    HalfVcc,
+    Diode,
+}
+
+// TIMER0_A3 capture mode for CAOUT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(unused)]
+pub enum CaptureEdge {
+    Rising,
+    Falling,
+    Both,
+}
+
+pub struct Comparator {
+    inner: {{device}}::COMPARATOR_A,
+}
+
+#[allow(unused)]
+impl Comparator {
+    // Starts switched off, with both terminals unconnected.
+    pub fn new(inner: {{device}}::COMPARATOR_A) -> Self {
+        inner.cactl1.write(|w| unsafe { w.bits(0) });
+        inner.cactl2.write(|w| unsafe { w.bits(0) });
+        inner.capd.write(|w| unsafe { w.bits(0) });
+
+        Comparator { inner }
+    }
+
+    // The pin's digital input is disabled for as long as it is connected.
+    pub fn connect_plus<P: PlusInput>(&mut self, _pin: &mut P) {
+        self.connect(PLUS_MASK, P::P2CA, P::CA);
+    }
+
+    pub fn connect_minus<P: MinusInput>(&mut self, _pin: &mut P) {
+        self.connect(MINUS_MASK, P::P2CA, P::CA);
+    }
+
+    fn connect(&mut self, mask: u8, p2ca: u8, ca: u8) {
+        // Re-enable the digital input of the pin previously connected to this terminal.
+        let prev = self.inner.cactl2.read().bits() & mask;
+        if let Some(prev_ca) = self.connected_ca(mask, prev) {
+            self.inner
+                .capd
+                .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << prev_ca)) });
+        }
+
+        self.inner
+            .capd
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << ca)) });
+        self.inner
+            .cactl2
+            .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | p2ca) });
+    }
+
+    fn connected_ca(&self, mask: u8, p2ca: u8) -> Option<u8> {
+        match (mask, p2ca) {
+            (_, 0) => None,
+            (PLUS_MASK, 0b0000_0100) => Some(0),
+            (PLUS_MASK, 0b0100_0000) => Some(1),
+            (PLUS_MASK, _) => Some(2),
+            (_, bits) => Some(bits >> 3),
+        }
+    }
+
+    // Apply an internal reference to a terminal. Leave that terminal without a pin connected.
+    pub fn set_reference(&mut self, reference: InternalRef, terminal: Terminal) {
+        self.inner.cactl1.modify(|_, w| {
+            let w = w.carsel().bit(terminal == Terminal::Minus);
+
+            match reference {
+                InternalRef::QuarterVcc => w.caref().caref_1(),
+                InternalRef::HalfVcc => w.caref().caref_2(),
+                InternalRef::Diode => w.caref().caref_3(),
+            }
+        });
+    }
+
+    pub fn clear_reference(&mut self) {
+        self.inner.cactl1.modify(|_, w| w.caref().caref_0());
+    }
+
+    // Swap the terminals and invert the output (CAEX). Chopping between both settings and
+    // averaging cancels the comparator's offset voltage.
+    pub fn set_exchange(&mut self, exchange: bool) {
+        self.inner.cactl1.modify(|_, w| w.caex().bit(exchange));
+    }
+
+    // RC filter on CAOUT against oscillation when the inputs are close together.
+    pub fn set_filter(&mut self, filter: bool) {
+        self.inner.cactl2.modify(|_, w| w.caf().bit(filter));
+    }
+
+    // Short the + and - terminals, e.g. to discharge a sample capacitor (CASHORT).
+    pub fn set_short(&mut self, short: bool) {
+        self.inner.cactl2.modify(|_, w| w.cashort().bit(short));
+    }
+
+    pub fn enable(&mut self) {
+        self.inner.cactl1.modify(|_, w| w.caon().set_bit());
+    }
+
+    // CAON only gates the comparator, so this also turns off the reference (CAREF) to stop all
+    // of the module's current draw. Set the reference again after `enable`.
+    pub fn disable(&mut self) {
+        self.inner
+            .cactl1
+            .modify(|_, w| w.caon().clear_bit().caie().clear_bit().caref().caref_0());
+    }
+
+    // + terminal above - terminal (inverted with `set_exchange`).
+    pub fn output(&self) -> bool {
+        self.inner.cactl2.read().caout().bit_is_set()
+    }
+
+    // Enable the COMPARATORA interrupt on an edge of CAOUT. Changing the edge can set CAIFG,
+    // so it is cleared before enabling the interrupt.
+    pub fn listen(&mut self, edge: Edge) {
+        self.inner
+            .cactl1
+            .modify(|_, w| w.caie().clear_bit().caies().bit(edge == Edge::Falling));
+        self.inner.cactl1.modify(|_, w| w.caifg().clear_bit());
+        self.inner.cactl1.modify(|_, w| w.caie().set_bit());
+    }
+
+    pub fn unlisten(&mut self) {
+        self.inner.cactl1.modify(|_, w| w.caie().clear_bit());
+    }
+
+    // For polling without the interrupt. CAIFG is cleared automatically when the COMPARATORA
+    // interrupt is serviced.
+    pub fn take_pending(_cs: mspint::CriticalSection) -> bool {
+        // SAFETY: Only CAIFG is read and cleared.
+        let ca = unsafe { &*{{device}}::COMPARATOR_A::ptr() };
+
+        let pending = ca.cactl1.read().caifg().bit_is_set();
+        ca.cactl1.modify(|_, w| w.caifg().clear_bit());
+        pending
+    }
+
+    // Capture TIMER0_A3 into TACCR1 on CAOUT edges, through CCI1B. Captures are synchronized to
+    // the timer clock. With `interrupt`, TIMER0_A1 fires on each capture.
+    pub fn capture_on(&self, timer: &{{device}}::TIMER0_A3, edge: CaptureEdge, interrupt: bool) {
+        timer.tacctl1.write(|w| {
+            let w = w
+                .cap()
+                .set_bit()
+                .scs()
+                .set_bit()
+                .ccis()
+                .ccis_1()
+                .ccie()
+                .bit(interrupt);
+
+            match edge {
+                CaptureEdge::Rising => w.cm().cm_1(),
+                CaptureEdge::Falling => w.cm().cm_2(),
+                CaptureEdge::Both => w.cm().cm_3(),
+            }
+        });
+    }
+
+    pub fn capture_off(&self, timer: &{{device}}::TIMER0_A3) {
+        timer.tacctl1.write(|w| w.cm().cm_0());
+    }
+
+    // Latest capture, if one happened since the last call. `Err` carries the capture if an
+    // earlier one was overwritten before being read (COV).
+    pub fn take_capture(timer: &{{device}}::TIMER0_A3) -> Option<Result<u16, u16>> {
+        let ctl = timer.tacctl1.read();
+
+        if ctl.ccifg().bit_is_clear() {
+            return None;
+        }
+
+        let count = timer.taccr1.read().bits();
+        timer
+            .tacctl1
+            .modify(|_, w| w.ccifg().clear_bit().cov().clear_bit());
+
+        if ctl.cov().bit_is_set() {
+            Some(Err(count))
+        } else {
+            Some(Ok(count))
+        }
+    }
+}
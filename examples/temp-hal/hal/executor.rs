@@ -0,0 +1,115 @@
+// Minimal executor for `async fn` tasks. The tasks are a fixed set of futures, pinned on main's
+// stack, which is as good as static since `run` never returns:
+//
+//     let mut tasks: [Task; 2] = [pin!(blink(timer, led)), pin!(echo(serial))];
+//     executor::run(&mut tasks, LowPowerMode::Lpm0);
+//
+// Each task has a bit in a ready mask, and only tasks whose bit is set are polled. A task's
+// waker sets its bit. Futures waiting on hardware register their waker in a `WakerSlot`, which
+// the interrupt handler signals; the handler must be defined with `wake_interrupt!` and return
+// the result of `WakerSlot::wake`, so main also wakes from the low-power mode the executor
+// sleeps in while no task is ready.
+
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+
+use msp430::{interrupt as mspint, critical_section as mspcs};
+
+use super::lpm::{self, LowPowerMode};
+
+#[allow(unused)]
+pub const MAX_TASKS: usize = 16;
+
+#[allow(unused)]
+pub type Task<'a> = Pin<&'a mut dyn Future<Output = ()>>;
+
+#[allow(unused)]
+static READY: mspint::Mutex<Cell<u16>> = mspint::Mutex::new(Cell::new(0));
+
+#[allow(unused)]
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, wake_task, wake_task, drop_waker);
+
+// The waker's data is the task's index, not a pointer.
+#[allow(unused)]
+unsafe fn clone_waker(task: *const ()) -> RawWaker {
+    RawWaker::new(task, &VTABLE)
+}
+
+#[allow(unused)]
+unsafe fn wake_task(task: *const ()) {
+    mspcs::with(|cs| {
+        let ready = READY.borrow(cs);
+        ready.set(ready.get() | 1 << task as usize);
+    });
+}
+
+#[allow(unused)]
+unsafe fn drop_waker(_task: *const ()) {}
+
+#[allow(unused)]
+fn waker(task: usize) -> Waker {
+    // SAFETY: The vtable functions don't dereference the data.
+    unsafe { Waker::from_raw(RawWaker::new(task as *const (), &VTABLE)) }
+}
+
+// Poll every task once, then each task whenever it's woken. Finished tasks are never polled
+// again. Sleeps in `mode` while no task is ready; peripherals the tasks wait on must keep running
+// in it. Interrupts are enabled from the first time it sleeps on.
+#[allow(unused)]
+pub fn run(tasks: &mut [Task], mode: LowPowerMode) -> ! {
+    assert!(tasks.len() <= MAX_TASKS);
+
+    let all = (1u32 << tasks.len()).wrapping_sub(1) as u16;
+    let mut done = 0u16;
+    mspcs::with(|cs| READY.borrow(cs).set(all));
+
+    loop {
+        let ready = mspcs::with(|cs| READY.borrow(cs).replace(0)) & !done;
+
+        for (i, task) in tasks.iter_mut().enumerate() {
+            if ready & (1 << i) == 0 {
+                continue;
+            }
+
+            let waker = waker(i);
+            if task.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                done |= 1 << i;
+            }
+        }
+
+        lpm::sleep_unless(mode, |cs| READY.borrow(cs).get() & !done != 0);
+    }
+}
+
+// The waker of a future waiting for an interrupt. One future at a time can wait on a slot.
+pub struct WakerSlot {
+    waker: mspint::Mutex<Cell<Option<Waker>>>,
+}
+
+#[allow(unused)]
+impl WakerSlot {
+    pub const fn new() -> Self {
+        WakerSlot {
+            waker: mspint::Mutex::new(Cell::new(None)),
+        }
+    }
+
+    // Call before returning `Poll::Pending`, and before enabling the interrupt.
+    pub fn register(&self, cs: mspint::CriticalSection, waker: &Waker) {
+        self.waker.borrow(cs).set(Some(waker.clone()));
+    }
+
+    // Wake the waiting future, if any. Returns whether there was one, i.e. whether main should
+    // wake up.
+    pub fn wake(&self, cs: mspint::CriticalSection) -> bool {
+        match self.waker.borrow(cs).take() {
+            Some(waker) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+}
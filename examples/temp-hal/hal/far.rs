@@ -0,0 +1,137 @@
+// Constant data above the 64kB mark, in the `.far_rodata` section of memory.x.
+//
+// Rust's msp430 target has 16-bit pointers, so far data can't be referenced like a normal
+// static; the linker would reject the truncated address. Instead, `far_data!` keeps the 20-bit
+// address in a word pair in ROM, and `Far<T>` reads the data through it with the MSP430X
+// MOVA and MOVX instructions:
+//
+//     far_data! {
+//         static SINE: [u8; 256] = [...];
+//     }
+//
+//     let x = SINE.get(i).unwrap();
+//
+// Those instructions are hand-encoded, since the assembler doesn't know them, and only run for
+// addresses above 64kB. On parts without memory up there, memory.x places `.far_rodata` in ROM
+// and everything is read normally, so code using far data runs unchanged on both.
+//
+// Only data can be far. Calling far functions would take the MSP430X CALLA and RETA
+// instructions, which Rust doesn't generate.
+
+use core::arch::asm;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use msp430::critical_section as mspcs;
+
+// Define a static of type `Far<T>` whose value is placed in `.far_rodata`.
+#[macro_export]
+macro_rules! far_data {
+    ($vis:vis static $name:ident: $ty:ty = $init:expr;) => {
+        $vis static $name: $crate::hal::far::Far<$ty> = {
+            // Only ever referenced by the address below, never from Rust code.
+            #[used]
+            #[export_name = concat!("__far_data_", stringify!($name))]
+            #[link_section = concat!(".far_rodata.", stringify!($name))]
+            static DATA: $ty = $init;
+
+            extern "C" {
+                #[link_name = concat!("__far_addr_", stringify!($name))]
+                static ADDR: u32;
+            }
+
+            // SAFETY: The address is defined below and never written.
+            $crate::hal::far::Far::new(unsafe { &ADDR })
+        };
+
+        core::arch::global_asm!(concat!(
+            ".section .rodata.__far_addr_", stringify!($name), ",\"a\",@progbits\n",
+            ".balign 2\n",
+            "__far_addr_", stringify!($name), ":\n",
+            ".long __far_data_", stringify!($name), "\n",
+        ));
+    };
+}
+
+pub struct Far<T> {
+    addr: &'static u32,
+    _data: PhantomData<T>,
+}
+
+// SAFETY: The data is constant.
+unsafe impl<T: Sync> Sync for Far<T> {}
+
+#[allow(unused)]
+impl<T: Copy> Far<T> {
+    // Use `far_data!` rather than calling this directly.
+    pub const fn new(addr: &'static u32) -> Self {
+        Far {
+            addr,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn addr(&self) -> u32 {
+        *self.addr
+    }
+
+    pub fn read(&self) -> T {
+        // SAFETY: `far_data!` placed a T at the address.
+        unsafe { read_at(self.addr()) }
+    }
+}
+
+#[allow(unused)]
+impl<T: Copy, const N: usize> Far<[T; N]> {
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    // Reads a single element, rather than copying the whole array into RAM.
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= N {
+            return None;
+        }
+
+        let offset = (index * mem::size_of::<T>()) as u32;
+        // SAFETY: `far_data!` placed N Ts at the address, and the index is in range.
+        Some(unsafe { read_at(self.addr() + offset) })
+    }
+}
+
+// SAFETY: There must be a valid T at `addr`.
+unsafe fn read_at<T: Copy>(addr: u32) -> T {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes = value.as_mut_ptr() as *mut u8;
+
+    if addr + mem::size_of::<T>() as u32 <= 0x1_0000 {
+        ptr::copy_nonoverlapping(addr as u16 as *const u8, bytes, mem::size_of::<T>());
+    } else {
+        // The upper bits of the address register would be lost if an interrupt handler saved
+        // and restored it.
+        mspcs::with(|_| {
+            for i in 0..mem::size_of::<T>() {
+                *bytes.add(i) = read_far_byte(addr + i as u32);
+            }
+        });
+    }
+
+    value.assume_init()
+}
+
+// SAFETY: Must be run with interrupts disabled, on an MSP430X part.
+unsafe fn read_far_byte(addr: u32) -> u8 {
+    // MOVA loads a 20-bit address from a pair of words in memory.
+    let addr = [addr as u16, (addr >> 16) as u16];
+    let byte: u16;
+
+    asm!(
+        ".word 0x0C0C",         // mova @r12, r12
+        ".word 0x1840, 0x4C6C", // movx.b @r12, r12
+        inout("r12") addr.as_ptr() => byte,
+        options(nostack, readonly, preserves_flags),
+    );
+
+    byte as u8
+}
@@ -0,0 +1,215 @@
+// Flash memory controller driver. Addresses are those of the msp430g2553: 16 KiB of main memory
+// in 512-byte segments, and 256 bytes of info memory in the 64-byte segments D, C, B and A.
+//
+// The CPU is held while the controller erases or programs, so this works when running from
+// flash, as long as the code being run isn't what's being erased. Interrupts are disabled for
+// the duration of each operation, because their vectors and handlers can't be fetched from
+// flash in the meantime. A segment erase takes ~12ms; mind the watchdog.
+//
+// The FCTLx registers need the FWKEY password in their upper byte on every write. A write
+// without it sets KEYV and resets the part, so it can only be reported after the fact, by
+// `Flash::take_key_violation`.
+
+use core::ptr;
+
+use msp430::critical_section as mspcs;
+
+use super::clock::Clocks;
+
+pub const INFO_START: u16 = 0x1000;
+pub const INFO_END: u16 = 0x10FF;
+pub const INFO_SEGMENT_SIZE: u16 = 64;
+pub const INFO_A_START: u16 = 0x10C0;
+pub const MAIN_START: u16 = 0xC000;
+#[allow(unused)]
+pub const MAIN_SEGMENT_SIZE: u16 = 512;
+// The last main memory segment, holding the interrupt vectors and the reset vector. Erasing it
+// bricks the part until it's reprogrammed, so it's off limits.
+pub const VECTORS_START: u16 = 0xFE00;
+
+// Flash timing generator limits (fFTG).
+const FTG_MIN_HZ: u32 = 257_000;
+const FTG_MAX_HZ: u32 = 476_000;
+
+const FWKEY: u16 = 0xA500;
+// FCTL1
+const ERASE: u16 = 1 << 1;
+const MERAS: u16 = 1 << 2;
+const WRT: u16 = 1 << 6;
+// FCTL2
+const FSSEL_SMCLK: u16 = 0b10 << 6;
+// FCTL3
+const BUSY: u16 = 1 << 0;
+const KEYV: u16 = 1 << 1;
+const ACCVIFG: u16 = 1 << 2;
+const LOCK: u16 = 1 << 4;
+const LOCKA: u16 = 1 << 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlashError {
+    // No FNx divider brings SMCLK into the flash timing generator's range.
+    ClockOutOfRange,
+    // Address outside flash, misaligned, inside the vector segment, or inside segment A while
+    // it's locked.
+    InvalidAddress,
+    // ACCVIFG: flash was accessed in a way the controller doesn't allow during the operation.
+    AccessViolation,
+    // KEYV: an FCTLx write without the password caused the last reset.
+    KeyViolation,
+}
+
+pub struct Flash {
+    inner: {{device}}::FLASH,
+}
+
+#[allow(unused)]
+impl Flash {
+    // The flash timing generator runs from SMCLK, divided into 257-476 kHz.
+    pub fn new(inner: {{device}}::FLASH, clocks: &Clocks) -> Result<Self, FlashError> {
+        let div = clocks.smclk().div_ceil(FTG_MAX_HZ);
+        if div == 0 || div > 64 || clocks.smclk() / div < FTG_MIN_HZ {
+            return Err(FlashError::ClockOutOfRange);
+        }
+
+        inner
+            .fctl2
+            .write(|w| unsafe { w.bits(FWKEY | FSSEL_SMCLK | (div as u16 - 1)) });
+
+        Ok(Flash { inner })
+    }
+
+    // Report, once, a key violation that caused the last reset.
+    pub fn take_key_violation(&mut self) -> Result<(), FlashError> {
+        if self.fctl3() & KEYV != 0 {
+            self.write_fctl3(LOCK);
+            Err(FlashError::KeyViolation)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn fctl3(&self) -> u16 {
+        // The read value carries FRKEY in the upper byte.
+        self.inner.fctl3.read().bits() & 0x00FF
+    }
+
+    fn write_fctl1(&self, bits: u16) {
+        self.inner.fctl1.write(|w| unsafe { w.bits(FWKEY | bits) });
+    }
+
+    fn write_fctl3(&self, bits: u16) {
+        self.inner.fctl3.write(|w| unsafe { w.bits(FWKEY | bits) });
+    }
+
+    // LOCKA toggles when written with 1, and is left alone when written with 0.
+    pub fn unlock_info_a(&mut self) {
+        if self.fctl3() & LOCKA != 0 {
+            self.write_fctl3(LOCK | LOCKA);
+        }
+    }
+
+    pub fn lock_info_a(&mut self) {
+        if self.fctl3() & LOCKA == 0 {
+            self.write_fctl3(LOCK | LOCKA);
+        }
+    }
+
+    // `len` bytes from `addr` must all be in either info or main memory, short of the vectors.
+    fn check(&self, addr: u16, len: u16, align: u16) -> Result<(), FlashError> {
+        let end = addr
+            .checked_add(len.max(1) - 1)
+            .ok_or(FlashError::InvalidAddress)?;
+        let in_info = addr >= INFO_START && end <= INFO_END;
+        let in_main = addr >= MAIN_START && end < VECTORS_START;
+        let locked_a = in_info && end >= INFO_A_START && self.fctl3() & LOCKA != 0;
+
+        if (!in_info && !in_main) || !addr.is_multiple_of(align) || locked_a {
+            Err(FlashError::InvalidAddress)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Run `op` with the controller unlocked in the FCTL1 mode `mode`, then lock it again and
+    // report any access violation.
+    fn run(&mut self, mode: u16, op: impl FnOnce()) -> Result<(), FlashError> {
+        mspcs::with(|_cs| {
+            while self.fctl3() & BUSY != 0 {}
+
+            // Clearing LOCK while keeping LOCKA as is (writing 0 leaves it alone).
+            self.write_fctl3(0);
+            self.write_fctl1(mode);
+
+            op();
+
+            while self.fctl3() & BUSY != 0 {}
+
+            self.write_fctl1(0);
+            let fctl3 = self.fctl3();
+            self.write_fctl3(LOCK);
+
+            if fctl3 & ACCVIFG != 0 {
+                Err(FlashError::AccessViolation)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    // Erase the segment containing `addr`.
+    pub fn erase_segment(&mut self, addr: u16) -> Result<(), FlashError> {
+        self.check(addr, 1, 1)?;
+
+        // SAFETY: A dummy write into the segment starts the erase. `check` made sure the
+        // address is flash; a byte write, because it may be odd.
+        self.run(ERASE, || unsafe { ptr::write_volatile(addr as *mut u8, 0) })
+    }
+
+    // Erase all of main memory, including the code doing the erasing unless it runs from RAM.
+    // Only useful to bootloaders running elsewhere, e.g. the ROM BSL.
+    pub fn erase_main(&mut self) -> Result<(), FlashError> {
+        // SAFETY: As for `erase_segment`.
+        self.run(MERAS, || unsafe {
+            ptr::write_volatile(MAIN_START as *mut u16, 0)
+        })
+    }
+
+    // Programming can only clear bits; erase first for anything else.
+    pub fn write_byte(&mut self, addr: u16, byte: u8) -> Result<(), FlashError> {
+        self.check(addr, 1, 1)?;
+
+        // SAFETY: `check` made sure the address is flash.
+        self.run(WRT, || unsafe { ptr::write_volatile(addr as *mut u8, byte) })
+    }
+
+    pub fn write_word(&mut self, addr: u16, word: u16) -> Result<(), FlashError> {
+        self.check(addr, 2, 2)?;
+
+        // SAFETY: `check` made sure the address is word-aligned flash.
+        self.run(WRT, || unsafe { ptr::write_volatile(addr as *mut u16, word) })
+    }
+
+    // Write consecutive words starting at `addr`, within one unlock. The controller's block
+    // write mode (BLKWRT) would be ~2x faster, but needs the writing code to execute from RAM.
+    pub fn write_block(&mut self, addr: u16, words: &[u16]) -> Result<(), FlashError> {
+        self.check(addr, words.len() as u16 * 2, 2)?;
+
+        // SAFETY: `check` made sure the whole range is word-aligned flash.
+        self.run(WRT, || {
+            for (i, &word) in words.iter().enumerate() {
+                unsafe { ptr::write_volatile((addr + i as u16 * 2) as *mut u16, word) };
+            }
+        })
+    }
+}
+
+// Reads are plain memory reads.
+pub fn read_byte(addr: u16) -> u8 {
+    // SAFETY: All of the address space is readable.
+    unsafe { ptr::read_volatile(addr as *const u8) }
+}
+
+pub fn read_word(addr: u16) -> u16 {
+    // SAFETY: As above; the address is rounded down to an even one.
+    unsafe { ptr::read_volatile((addr & !1) as *const u16) }
+}
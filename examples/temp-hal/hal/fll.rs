@@ -0,0 +1,216 @@
+// Software frequency-locked loop for the DCO. For frequencies (or parts) without factory
+// calibration constants, the DCO is trimmed against a crystal-driven ACLK by counting SMCLK
+// cycles between ACLK captures on TIMER0_A3.
+
+use super::clock::{self, AclkSource, Clocks};
+
+// ACLK periods per measurement. With an undivided 32.768 kHz reference, this takes ~244us and
+// resolves the DCO frequency to 4096 Hz.
+const FLL_MEASURE_PERIODS: u16 = 8;
+// Highest DCO frequency the part is specified for.
+const FLL_MAX_HZ: u32 = 16_000_000;
+// Highest RSELx, DCOx and MODx settings.
+const RSEL_MAX: u8 = 15;
+const DCO_MAX: u8 = 7;
+const MOD_MAX: u8 = 31;
+
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum FllError {
+    // ACLK isn't running from the crystal, so there's nothing stable to lock to.
+    NoReference,
+    // The target is out of reach: of the DCO for `new` and `lock`, or of the current RSELx range
+    // for `trim`.
+    OutOfRange,
+    // The closest setting found is still further from the target than the deadband.
+    NotLocked,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FllStatus {
+    freq_hz: u32,
+    error_hz: i32,
+}
+
+#[allow(unused)]
+impl FllStatus {
+    // DCO frequency measured at the last step.
+    pub fn freq_hz(&self) -> u32 {
+        self.freq_hz
+    }
+
+    // Measured minus target frequency.
+    pub fn error_hz(&self) -> i32 {
+        self.error_hz
+    }
+}
+
+pub struct Fll {
+    clock: {{device}}::SYSTEM_CLOCK,
+    aclk: u32,
+    target_hz: u32,
+    target_counts: u32,
+    // Errors (in counts) up to this are accepted by `lock` and left alone by `trim`. About one
+    // MODx step.
+    deadband: u32,
+}
+
+#[allow(unused)]
+impl Fll {
+    // MCLK and SMCLK must be sourced from the undivided DCO (the reset default).
+    pub fn new(target_hz: u32, clocks: &Clocks) -> Result<Self, FllError> {
+        if clocks.aclk_source() != AclkSource::Lfxt1 {
+            return Err(FllError::NoReference);
+        }
+        if target_hz > FLL_MAX_HZ {
+            return Err(FllError::OutOfRange);
+        }
+
+        let target_counts = target_hz * u32::from(FLL_MEASURE_PERIODS) / clocks.aclk();
+
+        // SAFETY: The Fll only touches DCOCTL and the RSELx bits of BCSCTL1, which nothing
+        // else writes to once ClockConfig is frozen.
+        let clock = unsafe { {{device}}::Peripherals::steal().SYSTEM_CLOCK };
+
+        Ok(Fll {
+            clock,
+            aclk: clocks.aclk(),
+            target_hz,
+            target_counts,
+            deadband: target_counts / 256 + 1,
+        })
+    }
+
+    // Lock onto the target frequency, typically at startup. The RSELx ranges overlap, and MODx
+    // only mixes in the next DCOx tap up, so the three are searched one after the other: the
+    // highest range whose lowest tap is no faster than the target, then the highest tap in it,
+    // then the highest MODx. That's at most 16 measurements. On success, `clocks` is updated
+    // with the achieved frequency.
+    //
+    // TIMER0_A3 is borrowed for the measurements and left stopped afterwards.
+    pub fn lock(
+        &mut self,
+        timer: &{{device}}::TIMER0_A3,
+        clocks: &mut Clocks,
+    ) -> Result<FllStatus, FllError> {
+        self.set_dco(0, 0);
+        let rsel = self
+            .search(timer, RSEL_MAX, |fll, rsel| fll.set_rsel(rsel))
+            .ok_or(FllError::OutOfRange)?;
+        self.set_rsel(rsel);
+
+        let mut dco = self
+            .search(timer, DCO_MAX, |fll, dco| fll.set_dco(dco, 0))
+            .unwrap_or(0);
+        // MODx does nothing at the top tap. The next range up usually overlaps this one, and has
+        // a tap that leaves room for it.
+        if dco == DCO_MAX && rsel < RSEL_MAX {
+            self.set_rsel(rsel + 1);
+            match self.search(timer, DCO_MAX, |fll, dco| fll.set_dco(dco, 0)) {
+                Some(next) => dco = next,
+                None => self.set_rsel(rsel),
+            }
+        }
+
+        let modx = if dco < DCO_MAX {
+            self.search(timer, MOD_MAX, |fll, modx| fll.set_dco(dco, modx))
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        self.set_dco(dco, modx);
+
+        let counts = clock::measure_aclk(timer, FLL_MEASURE_PERIODS);
+        if counts.abs_diff(self.target_counts) > self.deadband {
+            return Err(FllError::NotLocked);
+        }
+
+        let status = self.status(counts);
+        clocks.set_dco(status.freq_hz);
+        Ok(status)
+    }
+
+    // Single measurement and at most one MODx step of correction. Call periodically (e.g. once
+    // a second) to follow temperature and supply drift once `lock` has succeeded.
+    #[allow(unused)]
+    pub fn trim(
+        &mut self,
+        timer: &{{device}}::TIMER0_A3,
+        clocks: &mut Clocks,
+    ) -> Result<FllStatus, FllError> {
+        let counts = clock::measure_aclk(timer, FLL_MEASURE_PERIODS);
+
+        if counts > self.target_counts + self.deadband {
+            self.adjust(false)?;
+        } else if counts + self.deadband < self.target_counts {
+            self.adjust(true)?;
+        }
+
+        let status = self.status(counts);
+        clocks.set_dco(status.freq_hz);
+        Ok(status)
+    }
+
+    fn status(&self, counts: u32) -> FllStatus {
+        let freq_hz = counts * self.aclk / u32::from(FLL_MEASURE_PERIODS);
+
+        FllStatus {
+            freq_hz,
+            error_hz: freq_hz as i32 - self.target_hz as i32,
+        }
+    }
+
+    // The highest `value` up to `max` at which the DCO, after `set(value)`, runs no faster than
+    // the target, or None if it's too fast even at 0. The frequency must rise with `value`.
+    fn search(
+        &self,
+        timer: &{{device}}::TIMER0_A3,
+        max: u8,
+        set: impl Fn(&Self, u8),
+    ) -> Option<u8> {
+        let (mut low, mut high) = (0, max);
+        let mut found = None;
+
+        while low <= high {
+            let mid = low + (high - low) / 2;
+            set(self, mid);
+
+            if clock::measure_aclk(timer, FLL_MEASURE_PERIODS) <= self.target_counts {
+                found = Some(mid);
+                low = mid + 1;
+            } else if mid == 0 {
+                break;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        found
+    }
+
+    fn set_rsel(&self, rsel: u8) {
+        self.clock.bcsctl1.modify(|_, w| w.rsel().bits(rsel));
+    }
+
+    fn set_dco(&self, dco: u8, modx: u8) {
+        self.clock.dcoctl.write(|w| w.bits(dco << 5 | modx));
+    }
+
+    // One MODx step, carrying into DCOx but not into RSELx, where the ranges overlap. MODx does
+    // nothing at the top tap, so that's as far up as it goes; `lock` again if it's not enough.
+    fn adjust(&mut self, up: bool) -> Result<(), FllError> {
+        let dcoctl = self.clock.dcoctl.read().bits();
+        let dcoctl = if up {
+            if dcoctl >= DCO_MAX << 5 {
+                return Err(FllError::OutOfRange);
+            }
+            dcoctl + 1
+        } else {
+            dcoctl.checked_sub(1).ok_or(FllError::OutOfRange)?
+        };
+
+        self.clock.dcoctl.write(|w| w.bits(dcoctl));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// Support for the FRAM layout of memory-fr5994.x. None of this applies to flash parts like the
+// G2xx3: there, `.persistent` isn't defined, and there is no MPU. The registers are accessed by
+// address, since the PAC of the part this HAL is written for doesn't have them.
+//
+// Variables that must survive power loss are declared with `persistent!`, and read and written
+// within critical sections:
+//
+//     persistent! {
+//         static BOOT_COUNT: u16 = 0;
+//     }
+//
+//     mspcs::with(|cs| BOOT_COUNT.set(cs, BOOT_COUNT.get(cs) + 1));
+//
+// Their initial values are written when the part is programmed, not at reset. A `T` larger than
+// a word can be torn by power loss in the middle of `set`.
+//
+// `mpu_init` protects code and constants from stray writes, e.g. through a bad pointer. Call it
+// early, from the application's pre-init hook:
+//
+//     #[pre_init]
+//     unsafe fn pre_init() {
+//         hal::fram::mpu_init();
+//     }
+
+use core::cell::UnsafeCell;
+use core::ptr;
+
+use msp430::interrupt as mspint;
+
+// MPU registers and bits, for `mpu_init`. The examples are all for flash parts, so none of them
+// use it.
+#[allow(unused)]
+mod mpu {
+    pub const MPUCTL0: *mut u16 = 0x05A0 as *mut u16;
+    pub const MPUCTL0_H: *mut u8 = 0x05A1 as *mut u8;
+    pub const MPUSEGB2: *mut u16 = 0x05A4 as *mut u16;
+    pub const MPUSEGB1: *mut u16 = 0x05A6 as *mut u16;
+    pub const MPUSAM: *mut u16 = 0x05A8 as *mut u16;
+
+    pub const MPUPW: u16 = 0xA500;
+    pub const MPUENA: u16 = 0x0001;
+
+    // Access rights in MPUSAM, shifted by 4 bits per segment. A violation with VS set causes a
+    // PUC instead of an NMI.
+    pub const R: u16 = 0x1;
+    pub const W: u16 = 0x2;
+    pub const X: u16 = 0x4;
+    pub const VS: u16 = 0x8;
+
+    pub const SEG1: u16 = 0;
+    pub const SEG2: u16 = 4;
+    pub const SEG3: u16 = 8;
+    pub const INFO: u16 = 12;
+}
+
+extern "C" {
+    static _mpu_border1: u8;
+    static _mpu_border2: u8;
+}
+
+// Define a static of type `Persistent<T>` placed in `.persistent`.
+#[macro_export]
+macro_rules! persistent {
+    ($vis:vis static $name:ident: $ty:ty = $init:expr;) => {
+        #[link_section = concat!(".persistent.", stringify!($name))]
+        $vis static $name: $crate::hal::fram::Persistent<$ty> =
+            $crate::hal::fram::Persistent::new($init);
+    };
+}
+
+pub struct Persistent<T> {
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: The value is only accessed within critical sections.
+unsafe impl<T: Copy + Send> Sync for Persistent<T> {}
+
+#[allow(unused)]
+impl<T: Copy> Persistent<T> {
+    // Use `persistent!` rather than calling this directly.
+    pub const fn new(value: T) -> Self {
+        Persistent {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn get(&self, _cs: mspint::CriticalSection) -> T {
+        // SAFETY: Within a critical section, nothing else accesses the value.
+        unsafe { ptr::read_volatile(self.value.get()) }
+    }
+
+    pub fn set(&self, _cs: mspint::CriticalSection, value: T) {
+        // SAFETY: As for `get`. `mpu_init` leaves the segment holding `.persistent` writable.
+        unsafe { ptr::write_volatile(self.value.get(), value) }
+    }
+}
+
+// Program the MPU segment borders from the symbols in memory-fr5994.x: `.persistent` read/write,
+// code, constants and vectors read/execute, and far constants read-only. Info memory stays
+// read/write. Any violation resets the part. Access to the MPU registers is closed afterwards,
+// until the next reset.
+//
+// SAFETY: Only for FR5xx/FR6xx parts linked with memory-fr5994.x or a layout following it.
+#[allow(unused)]
+pub unsafe fn mpu_init() {
+    use mpu::*;
+
+    // The symbols' addresses are the border values themselves.
+    let border1 = ptr::addr_of!(_mpu_border1) as u16;
+    let border2 = ptr::addr_of!(_mpu_border2) as u16;
+
+    ptr::write_volatile(MPUCTL0, MPUPW);
+    ptr::write_volatile(MPUSEGB2, border2);
+    ptr::write_volatile(MPUSEGB1, border1);
+    ptr::write_volatile(
+        MPUSAM,
+        (R | W | VS) << SEG1 | (R | X | VS) << SEG2 | (R | VS) << SEG3 | (R | W | VS) << INFO,
+    );
+    ptr::write_volatile(MPUCTL0, MPUPW | MPUENA);
+    // Anything but the password in the upper byte closes access to the MPU registers.
+    ptr::write_volatile(MPUCTL0_H, 0);
+}
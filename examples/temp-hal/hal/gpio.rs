@@ -0,0 +1,446 @@
+// Type-state GPIO for PORT_1_2. Each pin is its own type, with its configuration encoded in
+// the type parameter:
+//
+//   let pins = p.PORT_1_2.split();
+//   let mut led = pins.p1_0.into_push_pull_output();
+//   led.toggle().unwrap();
+//
+// Pins don't own any registers; they access the PORT_1_2 block through a raw pointer. This is
+// sound because `split` consumes PORT_1_2, and every read-modify-write happens inside a
+// critical section, so pins on the same port can be used from main and interrupts alike.
+//
+// Input pins can also generate PORT1/PORT2 interrupts on a selected edge. The handler takes
+// the set of pins that fired from the port's interrupt handle in `Parts`:
+//
+//   #[interrupt]
+//   fn PORT1(cs: CriticalSection) {
+//       for pin in PORT1_INT.borrow(cs).get().unwrap().take_pending() {
+//           match pin { 3 => ..., _ => {} }
+//       }
+//   }
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use embedded_hal::digital::blocking::{
+    InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin,
+};
+use embedded_hal::digital::ErrorType;
+use msp430::critical_section as mspcs;
+
+pub struct Input<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+pub struct Floating;
+pub struct PullUp;
+pub struct PullDown;
+
+pub struct Output<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+pub struct PushPull;
+
+// Peripheral function selected with PxSEL (and PxSEL2).
+pub struct Alternate<F> {
+    _function: PhantomData<F>,
+}
+
+// PxSEL = 1, PxSEL2 = 0
+pub struct Primary;
+// PxSEL = 1, PxSEL2 = 1
+pub struct Secondary;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+// Set of pins on one port whose interrupt flag was set, in ascending pin order when iterated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PinEvents(u8);
+
+#[allow(unused)]
+impl PinEvents {
+    pub fn contains(&self, pin: u8) -> bool {
+        self.0 & (1 << pin) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    // Accumulate events, e.g. into a static that main polls.
+    pub fn union(self, other: PinEvents) -> PinEvents {
+        PinEvents(self.0 | other.0)
+    }
+}
+
+impl Iterator for PinEvents {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        let pin = self.0.trailing_zeros() as u8;
+        self.0 &= !(1 << pin);
+        Some(pin)
+    }
+}
+
+pub trait GpioExt {
+    type Parts;
+
+    fn split(self) -> Self::Parts;
+}
+
+fn port() -> &'static {{device}}::port_1_2::RegisterBlock {
+    // SAFETY: See module comment.
+    unsafe { &*{{device}}::PORT_1_2::ptr() }
+}
+
+macro_rules! set_bits {
+    ($reg:expr, $mask:expr) => {
+        $reg.modify(|r, w| unsafe { w.bits(r.bits() | $mask) })
+    };
+}
+
+macro_rules! clear_bits {
+    ($reg:expr, $mask:expr) => {
+        $reg.modify(|r, w| unsafe { w.bits(r.bits() & !$mask) })
+    };
+}
+
+// Pin handed over to capacitive touch sensing. PxSEL2 is set (with PxSEL and PxDIR clear) only
+// while a measurement runs, turning the pin into a relaxation oscillator; see `captouch`.
+pub struct OscPin {
+    port: u8,
+    mask: u8,
+}
+
+impl OscPin {
+    pub(super) fn set_osc(&self, enable: bool) {
+        mspcs::with(|_| {
+            let p = port();
+
+            match (self.port, enable) {
+                (1, true) => set_bits!(p.p1sel2, self.mask),
+                (1, false) => clear_bits!(p.p1sel2, self.mask),
+                (_, true) => set_bits!(p.p2sel2, self.mask),
+                (_, false) => clear_bits!(p.p2sel2, self.mask),
+            }
+        });
+    }
+}
+
+macro_rules! gpio {
+    ($(
+        $PxInt:ident, $pxint:ident, $x:expr,
+        $pxin:ident, $pxout:ident, $pxdir:ident, $pxren:ident, $pxsel:ident, $pxsel2:ident,
+        $pxie:ident, $pxies:ident, $pxifg:ident: [
+            $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty),)+
+        ]
+    )+) => {
+        #[allow(unused)]
+        pub struct Parts {
+            $(pub $pxint: $PxInt,)+
+            $($(pub $pxi: $PXi<$MODE>,)+)+
+        }
+
+        impl GpioExt for {{device}}::PORT_1_2 {
+            type Parts = Parts;
+
+            fn split(self) -> Parts {
+                Parts {
+                    $($pxint: $PxInt { _private: () },)+
+                    $($($pxi: $PXi { _mode: PhantomData },)+)+
+                }
+            }
+        }
+
+        $(
+            // Pending interrupt flags of a whole port, for use in its interrupt handler.
+            pub struct $PxInt {
+                _private: (),
+            }
+
+            impl $PxInt {
+                // Another handle, for interrupt handlers elsewhere in the HAL that took over
+                // the port's interrupts; see `asynch`.
+                #[allow(unused)]
+                pub(super) fn steal() -> Self {
+                    $PxInt { _private: () }
+                }
+
+                // Returns every pin with both its interrupt flag and enable set, and clears
+                // only those flags. Other flags, including ones set while the handler runs
+                // (apart from the few cycles of the read-modify-write of PxIFG), are kept and
+                // cause the interrupt to be taken again after it returns.
+                #[allow(unused)]
+                pub fn take_pending(&self) -> PinEvents {
+                    mspcs::with(|_| {
+                        let p = port();
+                        let pending = p.$pxifg.read().bits() & p.$pxie.read().bits();
+                        clear_bits!(p.$pxifg, pending);
+                        PinEvents(pending)
+                    })
+                }
+            }
+        )+
+
+        $($(
+            pub struct $PXi<MODE> {
+                _mode: PhantomData<MODE>,
+            }
+
+            #[allow(unused)]
+            impl<MODE> $PXi<MODE> {
+                const MASK: u8 = 1 << $i;
+
+                pub fn into_floating_input(self) -> $PXi<Input<Floating>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxsel, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        clear_bits!(p.$pxdir, Self::MASK);
+                        clear_bits!(p.$pxren, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+
+                pub fn into_pull_up_input(self) -> $PXi<Input<PullUp>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxsel, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        clear_bits!(p.$pxdir, Self::MASK);
+                        set_bits!(p.$pxout, Self::MASK); // PxOUT selects pullup vs pulldown.
+                        set_bits!(p.$pxren, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+
+                pub fn into_pull_down_input(self) -> $PXi<Input<PullDown>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxsel, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        clear_bits!(p.$pxdir, Self::MASK);
+                        clear_bits!(p.$pxout, Self::MASK);
+                        set_bits!(p.$pxren, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+
+                // Initially low.
+                pub fn into_push_pull_output(self) -> $PXi<Output<PushPull>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxsel, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        clear_bits!(p.$pxren, Self::MASK);
+                        clear_bits!(p.$pxout, Self::MASK);
+                        set_bits!(p.$pxdir, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+
+                // PxDIR is left alone; some peripheral functions (e.g. Timer_A outputs) also
+                // need it set.
+                pub fn into_alternate_primary(self) -> $PXi<Alternate<Primary>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxren, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        set_bits!(p.$pxsel, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+
+                // Primary function with PxDIR set, as required for e.g. Timer_A outputs.
+                pub fn into_alternate_primary_output(self) -> $PXi<Alternate<Primary>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxren, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        set_bits!(p.$pxdir, Self::MASK);
+                        set_bits!(p.$pxsel, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+
+                pub fn into_pin_osc(self) -> OscPin {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxsel, Self::MASK);
+                        clear_bits!(p.$pxsel2, Self::MASK);
+                        clear_bits!(p.$pxdir, Self::MASK);
+                        clear_bits!(p.$pxren, Self::MASK);
+                    });
+
+                    OscPin {
+                        port: $x,
+                        mask: Self::MASK,
+                    }
+                }
+
+                pub fn into_alternate_secondary(self) -> $PXi<Alternate<Secondary>> {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxren, Self::MASK);
+                        set_bits!(p.$pxsel2, Self::MASK);
+                        set_bits!(p.$pxsel, Self::MASK);
+                    });
+
+                    $PXi { _mode: PhantomData }
+                }
+            }
+
+            #[allow(unused)]
+            impl<MODE> $PXi<Input<MODE>> {
+                // Changing PxIES can itself set PxIFG, so the interrupt is disabled while the
+                // edge changes and the flag is cleared before it's enabled again.
+                pub fn enable_interrupt(&mut self, edge: Edge) {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxie, Self::MASK);
+                        Self::write_edge(p, edge);
+                        clear_bits!(p.$pxifg, Self::MASK);
+                        set_bits!(p.$pxie, Self::MASK);
+                    });
+                }
+
+                pub fn disable_interrupt(&mut self) {
+                    mspcs::with(|_| {
+                        let p = port();
+                        clear_bits!(p.$pxie, Self::MASK);
+                        clear_bits!(p.$pxifg, Self::MASK);
+                    });
+                }
+
+                // Select a new edge without losing an interrupt that is already pending. Useful
+                // to catch both edges by flipping the edge from the handler.
+                pub fn set_edge(&mut self, edge: Edge) {
+                    mspcs::with(|_| {
+                        let p = port();
+                        let pending = p.$pxifg.read().bits() & Self::MASK;
+                        let enabled = p.$pxie.read().bits() & Self::MASK;
+
+                        clear_bits!(p.$pxie, Self::MASK);
+                        Self::write_edge(p, edge);
+                        clear_bits!(p.$pxifg, Self::MASK & !pending);
+                        set_bits!(p.$pxie, enabled);
+                    });
+                }
+
+                pub fn edge(&self) -> Edge {
+                    if port().$pxies.read().bits() & Self::MASK != 0 {
+                        Edge::Falling
+                    } else {
+                        Edge::Rising
+                    }
+                }
+
+                fn write_edge(p: &{{device}}::port_1_2::RegisterBlock, edge: Edge) {
+                    match edge {
+                        Edge::Rising => clear_bits!(p.$pxies, Self::MASK),
+                        Edge::Falling => set_bits!(p.$pxies, Self::MASK),
+                    }
+                }
+            }
+
+            impl<MODE> ErrorType for $PXi<Input<MODE>> {
+                type Error = Infallible;
+            }
+
+            impl<MODE> InputPin for $PXi<Input<MODE>> {
+                fn is_high(&self) -> Result<bool, Self::Error> {
+                    Ok(port().$pxin.read().bits() & Self::MASK != 0)
+                }
+
+                fn is_low(&self) -> Result<bool, Self::Error> {
+                    Ok(port().$pxin.read().bits() & Self::MASK == 0)
+                }
+            }
+
+            impl<MODE> ErrorType for $PXi<Output<MODE>> {
+                type Error = Infallible;
+            }
+
+            impl<MODE> OutputPin for $PXi<Output<MODE>> {
+                fn set_low(&mut self) -> Result<(), Self::Error> {
+                    mspcs::with(|_| clear_bits!(port().$pxout, Self::MASK));
+                    Ok(())
+                }
+
+                fn set_high(&mut self) -> Result<(), Self::Error> {
+                    mspcs::with(|_| set_bits!(port().$pxout, Self::MASK));
+                    Ok(())
+                }
+            }
+
+            impl<MODE> StatefulOutputPin for $PXi<Output<MODE>> {
+                fn is_set_high(&self) -> Result<bool, Self::Error> {
+                    Ok(port().$pxout.read().bits() & Self::MASK != 0)
+                }
+
+                fn is_set_low(&self) -> Result<bool, Self::Error> {
+                    Ok(port().$pxout.read().bits() & Self::MASK == 0)
+                }
+            }
+
+            impl<MODE> ToggleableOutputPin for $PXi<Output<MODE>> {
+                fn toggle(&mut self) -> Result<(), Self::Error> {
+                    mspcs::with(|_| {
+                        port()
+                            .$pxout
+                            .modify(|r, w| unsafe { w.bits(r.bits() ^ Self::MASK) })
+                    });
+                    Ok(())
+                }
+            }
+        )+)+
+    };
+}
+
+gpio!(
+    Port1Interrupts, int1, 1,
+    p1in, p1out, p1dir, p1ren, p1sel, p1sel2,
+    p1ie, p1ies, p1ifg: [
+        P1_0: (p1_0, 0, Input<Floating>),
+        P1_1: (p1_1, 1, Input<Floating>),
+        P1_2: (p1_2, 2, Input<Floating>),
+        P1_3: (p1_3, 3, Input<Floating>),
+        P1_4: (p1_4, 4, Input<Floating>),
+        P1_5: (p1_5, 5, Input<Floating>),
+        P1_6: (p1_6, 6, Input<Floating>),
+        P1_7: (p1_7, 7, Input<Floating>),
+    ]
+    Port2Interrupts, int2, 2,
+    p2in, p2out, p2dir, p2ren, p2sel, p2sel2,
+    p2ie, p2ies, p2ifg: [
+        P2_0: (p2_0, 0, Input<Floating>),
+        P2_1: (p2_1, 1, Input<Floating>),
+        P2_2: (p2_2, 2, Input<Floating>),
+        P2_3: (p2_3, 3, Input<Floating>),
+        P2_4: (p2_4, 4, Input<Floating>),
+        P2_5: (p2_5, 5, Input<Floating>),
+        // P2SEL resets to 0xC0 so that XIN/XOUT are ready for the crystal.
+        P2_6: (p2_6, 6, Alternate<Primary>),
+        P2_7: (p2_7, 7, Alternate<Primary>),
+    ]
+);
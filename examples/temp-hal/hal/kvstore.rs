@@ -0,0 +1,325 @@
+// Small key-value store for settings, in info segments D, C and B (segment A holds the factory
+// calibration and is left alone).
+//
+// One segment is active at a time and holds an append-only log of records; the latest valid
+// record for a key wins. When the active segment is full, the latest value of every key is
+// copied into the next segment in rotation (D, C, B, D, ...), which then becomes active. Erases
+// are thus spread over all three segments.
+//
+// Segment layout, in words:
+//   0: SEGMENT_MAGIC, written last when a segment is set up, which makes it valid.
+//   1: Generation; the valid segment with the newest generation is the active one.
+//   2..: Records: a header word (key in the low byte, value length in bytes in the high byte),
+//        the value padded to whole words, and a CRC-16 over the header and value.
+//
+// Power loss in the middle of a write leaves either a record with a bad CRC, which is skipped,
+// or words that aren't erased after the end of the log; appends check for those and compact
+// first. Power loss during compaction leaves the new segment without its magic word, so the
+// old one stays active.
+
+use super::flash::{self, Flash, FlashError, INFO_SEGMENT_SIZE, INFO_START};
+
+const SEGMENTS: [u16; 3] = [INFO_START, INFO_START + 0x40, INFO_START + 0x80];
+const SEGMENT_MAGIC: u16 = 0x4B56;
+const RECORDS_OFFSET: u16 = 4;
+const ERASED: u16 = 0xFFFF;
+
+pub const MAX_VALUE_LEN: usize = 16;
+// 0xFF is what an erased header reads as.
+pub const MAX_KEY: u8 = 0xFE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KvError {
+    Flash(FlashError),
+    // Key above MAX_KEY, or value longer than MAX_VALUE_LEN.
+    Invalid,
+    // The latest values of all keys don't fit in one segment.
+    Full,
+}
+
+impl From<FlashError> for KvError {
+    fn from(e: FlashError) -> Self {
+        KvError::Flash(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Record {
+    addr: u16,
+    key: u8,
+    len: u8,
+}
+
+impl Record {
+    // Header, padded value and CRC.
+    fn size(len: u8) -> u16 {
+        2 + u16::from(len).div_ceil(2) * 2 + 2
+    }
+
+    fn value_addr(&self) -> u16 {
+        self.addr + 2
+    }
+
+    fn next_addr(&self) -> u16 {
+        self.addr + Record::size(self.len)
+    }
+}
+
+pub struct KvStore {
+    flash: Flash,
+    active: usize,
+    generation: u16,
+    // Address just past the last record in the active segment.
+    end: u16,
+}
+
+#[allow(unused)]
+impl KvStore {
+    // Find the active segment, or set up an empty store if there is none (e.g. first boot).
+    pub fn mount(flash: Flash) -> Result<Self, KvError> {
+        let mut active: Option<(usize, u16)> = None;
+
+        for (i, &seg) in SEGMENTS.iter().enumerate() {
+            if flash::read_word(seg) != SEGMENT_MAGIC {
+                continue;
+            }
+
+            let generation = flash::read_word(seg + 2);
+            match active {
+                Some((_, newest)) if !is_newer(generation, newest) => {}
+                _ => active = Some((i, generation)),
+            }
+        }
+
+        let mut store = KvStore {
+            flash,
+            active: 0,
+            generation: 0,
+            end: 0,
+        };
+
+        match active {
+            Some((i, generation)) => {
+                store.active = i;
+                store.generation = generation;
+                store.end = store.records().fold(store.records_start(), |_, r| r.next_addr());
+            }
+            None => store.format(0, 0)?,
+        }
+
+        Ok(store)
+    }
+
+    // Copy the latest value of `key` into `buf`, returning its length. `buf` must be large
+    // enough for the value; anything beyond its length is cut off.
+    pub fn get(&self, key: u8, buf: &mut [u8]) -> Option<usize> {
+        let r = self.find(key)?;
+        if r.len == 0 {
+            return None; // Removed.
+        }
+
+        let n = usize::from(r.len).min(buf.len());
+        for (i, b) in buf[..n].iter_mut().enumerate() {
+            *b = flash::read_byte(r.value_addr() + i as u16);
+        }
+
+        Some(usize::from(r.len))
+    }
+
+    pub fn set(&mut self, key: u8, value: &[u8]) -> Result<(), KvError> {
+        if key > MAX_KEY || value.is_empty() || value.len() > MAX_VALUE_LEN {
+            return Err(KvError::Invalid);
+        }
+
+        // Rewriting an unchanged value is a common pattern; don't wear the flash for it.
+        let mut cur = [0; MAX_VALUE_LEN];
+        if self.get(key, &mut cur) == Some(value.len()) && &cur[..value.len()] == value {
+            return Ok(());
+        }
+
+        self.append(key, value)
+    }
+
+    pub fn remove(&mut self, key: u8) -> Result<(), KvError> {
+        match self.find(key) {
+            Some(r) if r.len != 0 => self.append(key, &[]),
+            _ => Ok(()),
+        }
+    }
+
+    fn segment(&self) -> u16 {
+        SEGMENTS[self.active]
+    }
+
+    fn records_start(&self) -> u16 {
+        self.segment() + RECORDS_OFFSET
+    }
+
+    fn segment_end(&self) -> u16 {
+        self.segment() + INFO_SEGMENT_SIZE
+    }
+
+    fn records(&self) -> impl Iterator<Item = Record> {
+        records(self.segment())
+    }
+
+    fn find(&self, key: u8) -> Option<Record> {
+        self.records().filter(|r| r.key == key).last()
+    }
+
+    fn append(&mut self, key: u8, value: &[u8]) -> Result<(), KvError> {
+        let size = Record::size(value.len() as u8);
+
+        if !self.is_erased(self.end, size) {
+            self.compact(Some((key, value)))
+        } else {
+            self.write_record(self.end, key, value)?;
+            self.end += size;
+            Ok(())
+        }
+    }
+
+    // Whether `size` bytes from `addr` are free to be written.
+    fn is_erased(&self, addr: u16, size: u16) -> bool {
+        addr + size <= self.segment_end()
+            && (addr..addr + size)
+                .step_by(2)
+                .all(|a| flash::read_word(a) == ERASED)
+    }
+
+    fn write_record(&mut self, addr: u16, key: u8, value: &[u8]) -> Result<(), KvError> {
+        let len = value.len() as u8;
+
+        self.flash
+            .write_word(addr, u16::from(key) | (u16::from(len) << 8))?;
+        for (i, chunk) in value.chunks(2).enumerate() {
+            let word = u16::from(chunk[0]) | (u16::from(*chunk.get(1).unwrap_or(&0xFF)) << 8);
+            self.flash.write_word(addr + 2 + i as u16 * 2, word)?;
+        }
+
+        let crc_addr = addr + Record::size(len) - 2;
+        self.flash.write_word(crc_addr, crc_of(addr, len))?;
+
+        Ok(())
+    }
+
+    // Move the latest value of every key, plus `extra` in place of its key's current value, to
+    // the next segment.
+    fn compact(&mut self, extra: Option<(u8, &[u8])>) -> Result<(), KvError> {
+        let old = self.segment();
+        let next = (self.active + 1) % SEGMENTS.len();
+        let generation = self.generation.wrapping_add(1);
+
+        self.flash.erase_segment(SEGMENTS[next])?;
+
+        let mut addr = SEGMENTS[next] + RECORDS_OFFSET;
+        let seg_end = SEGMENTS[next] + INFO_SEGMENT_SIZE;
+        let mut buf = [0; MAX_VALUE_LEN];
+
+        for (i, r) in records(old).enumerate() {
+            let superseded = records(old).skip(i + 1).any(|later| later.key == r.key);
+            let replaced = matches!(extra, Some((key, _)) if key == r.key);
+            if superseded || replaced || r.len == 0 {
+                continue;
+            }
+
+            let len = usize::from(r.len);
+            for (j, b) in buf[..len].iter_mut().enumerate() {
+                *b = flash::read_byte(r.value_addr() + j as u16);
+            }
+
+            if addr + Record::size(r.len) > seg_end {
+                return Err(KvError::Full);
+            }
+            self.write_record(addr, r.key, &buf[..len])?;
+            addr += Record::size(r.len);
+        }
+
+        if let Some((key, value)) = extra.filter(|(_, value)| !value.is_empty()) {
+            if addr + Record::size(value.len() as u8) > seg_end {
+                return Err(KvError::Full);
+            }
+            self.write_record(addr, key, value)?;
+            addr += Record::size(value.len() as u8);
+        }
+
+        // Commit: the new segment becomes valid, and newer than the old one.
+        self.flash.write_word(SEGMENTS[next] + 2, generation)?;
+        self.flash.write_word(SEGMENTS[next], SEGMENT_MAGIC)?;
+
+        self.active = next;
+        self.generation = generation;
+        self.end = addr;
+
+        // Not required for correctness, but keeps a stale segment from ever being mistaken for
+        // a newer one after the generation counter wraps around.
+        self.flash.erase_segment(old)?;
+
+        Ok(())
+    }
+
+    fn format(&mut self, index: usize, generation: u16) -> Result<(), KvError> {
+        let seg = SEGMENTS[index];
+
+        self.flash.erase_segment(seg)?;
+        self.flash.write_word(seg + 2, generation)?;
+        self.flash.write_word(seg, SEGMENT_MAGIC)?;
+
+        self.active = index;
+        self.generation = generation;
+        self.end = seg + RECORDS_OFFSET;
+        Ok(())
+    }
+}
+
+// Valid records of the segment at `seg` in log order. Stops at the first erased header, or one
+// whose length doesn't fit in the segment.
+fn records(seg: u16) -> impl Iterator<Item = Record> {
+    let seg_end = seg + INFO_SEGMENT_SIZE;
+    let mut addr = seg + RECORDS_OFFSET;
+
+    core::iter::from_fn(move || loop {
+        if addr + 2 > seg_end {
+            return None;
+        }
+
+        let header = flash::read_word(addr);
+        let (key, len) = (header as u8, (header >> 8) as u8);
+        if header == ERASED || usize::from(len) > MAX_VALUE_LEN {
+            return None;
+        }
+
+        let r = Record { addr, key, len };
+        if r.next_addr() > seg_end {
+            return None;
+        }
+        addr = r.next_addr();
+
+        if crc_of(r.addr, r.len) == flash::read_word(r.next_addr() - 2) {
+            return Some(r);
+        }
+    })
+}
+
+// Generation `a` is newer than `b`, allowing for wraparound.
+fn is_newer(a: u16, b: u16) -> bool {
+    (a.wrapping_sub(b) as i16) > 0
+}
+
+// CRC-16/CCITT-FALSE over a record's header and value as stored in flash.
+fn crc_of(addr: u16, len: u8) -> u16 {
+    let mut crc = 0xFFFFu16;
+
+    for a in addr..addr + 2 + u16::from(len) {
+        crc ^= u16::from(flash::read_byte(a)) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
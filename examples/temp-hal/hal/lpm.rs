@@ -0,0 +1,146 @@
+// Low-power modes. Entering one sets bits in the status register, which stops the CPU and, the
+// deeper the mode, more of the clocks. An interrupt temporarily wakes the CPU to run its
+// handler; unless the handler clears those bits in the status register saved on its stack, the
+// CPU goes back to sleep on return. Handlers defined with `wake_interrupt!` can do that, so main
+// resumes after the `sleep_unless` or `enter` that put it to sleep:
+//
+//     wake_interrupt!(TIMER0_A1, timer_tick);
+//
+//     fn timer_tick(cs: CriticalSection) -> bool {
+//         // Handle the interrupt, then return whether main should wake up.
+//         true
+//     }
+//
+// Peripherals clocked from a clock that the mode stops also stop, and can't wake the part. In
+// particular, SMCLK is off from LPM2 on, so make sure e.g. the UART has finished sending first.
+
+use core::arch::asm;
+
+use msp430::interrupt as mspint;
+
+const GIE: u16 = 0x0008;
+const CPUOFF: u16 = 0x0010;
+const OSCOFF: u16 = 0x0020;
+const SCG0: u16 = 0x0040;
+const SCG1: u16 = 0x0080;
+
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum LowPowerMode {
+    // CPU and MCLK off. SMCLK and ACLK keep running, and so does the DCO if it sources SMCLK.
+    // Wakes up immediately; use it when peripherals on SMCLK must keep running.
+    Lpm0,
+    // As LPM0, but the DC generator of the DCO is turned off if the DCO doesn't source SMCLK.
+    Lpm1,
+    // CPU, MCLK and SMCLK off. ACLK keeps running. The DC generator of the DCO stays on, so the
+    // DCO restarts quickly on wakeup.
+    Lpm2,
+    // CPU, MCLK, SMCLK and the DCO off. Only ACLK, from the VLO or a 32kHz crystal, keeps
+    // running, so timers and the watchdog on ACLK still wake the part. The usual sleep mode.
+    Lpm3,
+    // Everything off, including ACLK and the crystal oscillator. Only interrupts not needing a
+    // clock, like port interrupts, wake the part. A crystal may take a while to restart.
+    Lpm4,
+}
+
+impl LowPowerMode {
+    fn bits(self) -> u16 {
+        match self {
+            LowPowerMode::Lpm0 => CPUOFF,
+            LowPowerMode::Lpm1 => SCG0 | CPUOFF,
+            LowPowerMode::Lpm2 => SCG1 | CPUOFF,
+            LowPowerMode::Lpm3 => SCG1 | SCG0 | CPUOFF,
+            LowPowerMode::Lpm4 => SCG1 | SCG0 | OSCOFF | CPUOFF,
+        }
+    }
+}
+
+// Enable interrupts and sleep until an interrupt handler wakes main up. Interrupts stay enabled
+// on return.
+//
+// An interrupt that arrives after main decided to sleep, but before it does, won't wake it. Use
+// `sleep_unless` to check for work without that race.
+pub fn enter(mode: LowPowerMode) {
+    // SAFETY: Setting GIE and the mode bits in one instruction means any interrupt pending from
+    // here on is taken while asleep, and its handler's wakeup isn't lost. The NOP is required
+    // after changing GIE.
+    unsafe {
+        asm!(
+            "bis.w {0}, r2",
+            "nop",
+            in(reg) mode.bits() | GIE,
+            options(nostack),
+        )
+    };
+}
+
+// Sleep unless `ready` says there is work to do. `ready` runs with interrupts disabled, and they
+// are re-enabled in the same instruction that puts the CPU to sleep, so a wakeup can't slip in
+// between the check and sleeping. Call in a loop, doing the work after each call:
+//
+//     loop {
+//         lpm::sleep_unless(LowPowerMode::Lpm3, |cs| has_work(cs));
+//         do_work();
+//     }
+//
+// Must not be called within a critical section, since interrupts are enabled on return.
+pub fn sleep_unless<F: FnOnce(mspint::CriticalSection) -> bool>(mode: LowPowerMode, ready: F) {
+    mspint::disable();
+
+    // SAFETY: Interrupts were just disabled.
+    if ready(unsafe { mspint::CriticalSection::new() }) {
+        // SAFETY: Not called within a critical section, per the above.
+        unsafe { mspint::enable() };
+    } else {
+        enter(mode);
+    }
+}
+
+// Define the handler for an interrupt vector that can wake main from a low-power mode. The
+// handler is a `fn(CriticalSection) -> bool`, or a closure that coerces to one; if it returns
+// true, main wakes up once it returns. Use instead of `#[interrupt]`, not together with it.
+//
+// A small assembly stub is the actual handler, since the status register saved on the stack
+// is only at a known offset there.
+#[macro_export]
+macro_rules! wake_interrupt {
+    ($vector:ident, $handler:expr) => {
+        const _: () = {
+            // Rejects names that aren't interrupt vectors of the device.
+            let _ = {{device}}::Interrupt::$vector;
+
+            #[export_name = concat!("__wake_", stringify!($vector))]
+            extern "C" fn handler() -> u16 {
+                // SAFETY: Interrupts are disabled while a handler runs.
+                let cs = unsafe { msp430::interrupt::CriticalSection::new() };
+                let handler: fn(msp430::interrupt::CriticalSection) -> bool = $handler;
+                handler(cs) as u16
+            }
+        };
+
+        // Saves the registers the handler may clobber, then clears the mode bits (SCG1, SCG0,
+        // OSCOFF and CPUOFF) of the status register saved above them if the handler asks to.
+        core::arch::global_asm!(concat!(
+            ".section .text.", stringify!($vector), ",\"ax\",@progbits\n",
+            ".global ", stringify!($vector), "\n",
+            ".balign 2\n",
+            stringify!($vector), ":\n",
+            "push r11\n",
+            "push r12\n",
+            "push r13\n",
+            "push r14\n",
+            "push r15\n",
+            "call #__wake_", stringify!($vector), "\n",
+            "tst r12\n",
+            "jz 1f\n",
+            "bic #0xf0, 10(r1)\n",
+            "1:\n",
+            "pop r15\n",
+            "pop r14\n",
+            "pop r13\n",
+            "pop r12\n",
+            "pop r11\n",
+            "reti\n",
+        ));
+    };
+}
@@ -5,6 +5,34 @@ use embedded_hal::serial::{self, nb::Write as SerWrite};
 use nb::Error as NbError;
 use nb::Result as NbResult;
 
+pub mod adc;
+pub mod app;
+pub mod asynch;
+pub mod button;
+pub mod captouch;
+pub mod clock;
+pub mod comparator;
+pub mod executor;
+pub mod far;
+pub mod flash;
+pub mod fram;
+pub mod fll;
+pub mod gpio;
+pub mod kvstore;
+pub mod lpm;
+pub mod nested;
+pub mod noinit;
+pub mod pwm;
+pub mod shared;
+pub mod spsc;
+pub mod stack;
+pub mod supply;
+pub mod temp_sensor;
+pub mod tlv;
+
+use clock::Clocks;
+use gpio::{Alternate, Secondary, P1_1, P1_2, P1_6, P1_7};
+
 pub struct Timer {
     inner: {{device}}::TIMER0_A3,
     elapsed: bool,
@@ -34,6 +62,21 @@ impl Timer {
         Ok(())
     }
 
+    // Like `start`, but in milliseconds of ACLK, using the (possibly measured) ACLK frequency
+    // from `clocks`. Saturates at the longest period the 16-bit timer can count.
+    #[allow(unused)]
+    pub fn start_ms(&mut self, clocks: &Clocks, ms: u16) -> Result<(), Infallible> {
+        let ticks = clocks.aclk() * u32::from(ms) / 1000;
+        // In up mode, the period is TACCR0 + 1 ticks.
+        let count = ticks.saturating_sub(1).min(u32::from(u16::MAX)) as u16;
+        self.start(count)
+    }
+
+    // Whether `wait` would return Ok, without consuming the tick.
+    pub fn is_elapsed(&self) -> bool {
+        self.elapsed
+    }
+
     pub fn wait(&mut self) -> NbResult<(), Infallible> {
         if self.elapsed {
             self.elapsed = false;
@@ -46,20 +89,43 @@ impl Timer {
 
 pub struct Serial {
     inner: {{device}}::USCI_A0_UART_MODE,
+    _tx: P1_2<Alternate<Secondary>>,
+    _rx: P1_1<Alternate<Secondary>>,
 }
 
 impl Serial {
-    pub fn new(inner: {{device}}::USCI_A0_UART_MODE) -> Self {
+    // UCA0TXD and UCA0RXD are only available on P1.2 and P1.1, which are switched to their
+    // secondary function here.
+    pub fn new<TX, RX>(
+        inner: {{device}}::USCI_A0_UART_MODE,
+        tx: P1_2<TX>,
+        rx: P1_1<RX>,
+        clocks: &Clocks,
+    ) -> Self {
+        const BAUD: u32 = 9600;
+
+        // Low-frequency baud rate mode. Only accurate with a calibrated DCO; the uncalibrated
+        // ~1.1 MHz DCO is too far off for the computed divider to work reliably.
+        let br = clocks.smclk() / BAUD; // INT(SMCLK/9600), 104 at 1 MHz.
+        let brs = ((clocks.smclk() % BAUD) * 8 + BAUD / 2) / BAUD; // ROUND(8*FRAC(SMCLK/9600)).
+
         inner.uca0ctl1.modify(|_, w| w.ucswrst().set_bit());
-        inner.uca0ctl1.modify(|_, w| w.ucssel().ucssel_2()); // Submain clock for UART (1.1 MHz)
+        inner.uca0ctl1.modify(|_, w| w.ucssel().ucssel_2()); // Submain clock for UART
         inner.uca0ctl0.modify(|_, w| w.ucsync().clear_bit()); // UART mode
-        inner.uca0br0.write(|w| w.bits(110)); // INT(1.1MHz/9600) = 114, but this worked better for me.
-        inner.uca0br1.write(|w| w.bits(0));
-        inner.uca0mctl.modify(|_, w| w.ucbrs().bits(0)); // ROUND(8*(1.1MHz/9600 - INT(1.1MHz/9600))) = 5,
-                                                         // but this worked better for me.
+        inner.uca0br0.write(|w| w.bits(br as u8));
+        inner.uca0br1.write(|w| w.bits((br >> 8) as u8));
+        inner.uca0mctl.modify(|_, w| w.ucbrs().bits(brs as u8));
+
+        // Hand the pins over before the USCI leaves reset and starts driving them.
+        let tx = tx.into_alternate_secondary();
+        let rx = rx.into_alternate_secondary();
         inner.uca0ctl1.modify(|_, w| w.ucswrst().clear_bit());
 
-        Serial { inner }
+        Serial {
+            inner,
+            _tx: tx,
+            _rx: rx,
+        }
     }
 }
 
@@ -73,8 +139,13 @@ impl SerWrite<u8> for Serial {
         }
     }
 
+    // Done once the last byte has been shifted out, e.g. before stopping SMCLK.
     fn flush(&mut self) -> NbResult<(), Self::Error> {
-        Ok(())
+        if self.inner.uca0stat.read().ucbusy().bit_is_set() {
+            Err(NbError::WouldBlock)
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -85,22 +156,43 @@ impl serial::ErrorType for Serial {
 pub struct I2c {
     inner: {{device}}::USCI_B0_I2C_MODE,
     ifg: Ucb0Ifg,
+    _scl: P1_6<Alternate<Secondary>>,
+    _sda: P1_7<Alternate<Secondary>>,
 }
 
 impl I2c {
-    pub fn new(inner: {{device}}::USCI_B0_I2C_MODE, ifg: Ucb0Ifg) -> Self {
+    // UCB0SCL and UCB0SDA are only available on P1.6 and P1.7.
+    pub fn new<SCL, SDA>(
+        inner: {{device}}::USCI_B0_I2C_MODE,
+        scl: P1_6<SCL>,
+        sda: P1_7<SDA>,
+        ifg: Ucb0Ifg,
+        clocks: &Clocks,
+    ) -> Self {
+        const SCL_HZ: u32 = 100_000;
+
+        let br = clocks.smclk() / SCL_HZ; // INT(SMCLK/100kHz), 10 at 1 MHz.
+
         inner.ucb0ctl1.modify(|_, w| w.ucswrst().set_bit());
-        inner.ucb0ctl1.modify(|_, w| w.ucssel().ucssel_2()); // Submain clock for I2C (1.1 MHz)
+        inner.ucb0ctl1.modify(|_, w| w.ucssel().ucssel_2()); // Submain clock for I2C
         inner
             .ucb0ctl0
             .modify(|_, w| w.ucsync().set_bit().ucmode().ucmode_3().ucmst().set_bit()); // I2C mode
 
-        inner.ucb0br0.write(|w| w.bits(11)); // INT(1.1MHz/11) = 100kHz
-        inner.ucb0br1.write(|w| w.bits(0));
+        inner.ucb0br0.write(|w| w.bits(br as u8));
+        inner.ucb0br1.write(|w| w.bits((br >> 8) as u8));
 
+        // Hand the pins over before the USCI leaves reset and starts driving them.
+        let scl = scl.into_alternate_secondary();
+        let sda = sda.into_alternate_secondary();
         inner.ucb0ctl1.modify(|_, w| w.ucswrst().clear_bit());
 
-        I2c { inner, ifg }
+        I2c {
+            inner,
+            ifg,
+            _scl: scl,
+            _sda: sda,
+        }
     }
 }
 
@@ -236,6 +328,26 @@ impl WatchdogTimer {
         Ok(self)
     }
 
+    // Interval timer mode: no resets, the WDT interrupt fires every `divider` ACLK cycles
+    // instead. Enable it with `WdtIe`.
+    #[allow(unused)]
+    pub fn start_interval(self, divider: WatchdogDivider) -> Result<Self, Infallible> {
+        self.inner.wdtctl.write(|w| {
+            w.wdtpw()
+                .password()
+                .wdttmsel()
+                .set_bit()
+                .wdtssel()
+                .set_bit()
+                .wdtcntcl()
+                .set_bit()
+                .wdtis()
+                .bits(divider as u8)
+        });
+
+        Ok(self)
+    }
+
     pub fn disable(self) -> Result<Self, Infallible> {
         self.inner
             .wdtctl
@@ -253,6 +365,7 @@ impl WatchdogTimer {
 }
 
 #[repr(u8)]
+#[derive(Clone, Copy)]
 #[allow(unused)]
 pub enum WatchdogDivider {
     By32768 = 0,
@@ -265,6 +378,12 @@ pub enum WatchdogDivider {
 // HAL impls can only access the registers they need. Functionality implemented on as-needed basis.
 pub struct SfrIfg {
     pub ucb0ifg: Ucb0Ifg,
+    pub osc_fault: clock::OscFault,
+    pub wdtie: WdtIe,
+    #[allow(unused)]
+    pub wdtifg: WdtIfg,
+    #[allow(unused)]
+    pub reset_flags: noinit::ResetFlags,
 }
 
 impl SfrIfg {
@@ -279,11 +398,27 @@ impl SfrIfg {
         let ucb0rxifg =
             unsafe { Ucb0RxIfg::new({{device}}::Peripherals::steal().SPECIAL_FUNCTION) };
 
+        // SAFETY: As above. OscFault only touches the LFXT1S bits of SYSTEM_CLOCK, and only
+        // from the NMI handler once the crystal has already been started.
+        let osc_fault = unsafe {
+            let p = {{device}}::Peripherals::steal();
+            clock::OscFault::new(p.SPECIAL_FUNCTION, p.SYSTEM_CLOCK)
+        };
+
+        let wdtie = unsafe { WdtIe::new({{device}}::Peripherals::steal().SPECIAL_FUNCTION) };
+        let wdtifg = unsafe { WdtIfg::new({{device}}::Peripherals::steal().SPECIAL_FUNCTION) };
+        let reset_flags =
+            unsafe { noinit::ResetFlags::new({{device}}::Peripherals::steal().SPECIAL_FUNCTION) };
+
         SfrIfg {
             ucb0ifg: Ucb0Ifg {
                 ucb0txifg,
                 ucb0rxifg,
             },
+            osc_fault,
+            wdtie,
+            wdtifg,
+            reset_flags,
         }
     }
 }
@@ -330,3 +465,43 @@ impl Ucb0RxIfg {
         self.inner.ifg2.read().ucb0rxifg().bit_is_clear()
     }
 }
+
+pub struct WdtIe {
+    inner: {{device}}::SPECIAL_FUNCTION,
+}
+
+impl WdtIe {
+    fn new(inner: {{device}}::SPECIAL_FUNCTION) -> Self {
+        WdtIe { inner }
+    }
+
+    #[allow(unused)]
+    pub fn enable(&mut self) {
+        self.inner.ie1.modify(|_, w| w.wdtie().set_bit());
+    }
+
+    #[allow(unused)]
+    pub fn disable(&mut self) {
+        self.inner.ie1.modify(|_, w| w.wdtie().clear_bit());
+    }
+}
+
+pub struct WdtIfg {
+    inner: {{device}}::SPECIAL_FUNCTION,
+}
+
+impl WdtIfg {
+    fn new(inner: {{device}}::SPECIAL_FUNCTION) -> Self {
+        WdtIfg { inner }
+    }
+
+    #[allow(unused)]
+    pub fn bit_is_set(&self) -> bool {
+        self.inner.ifg1.read().wdtifg().bit_is_set()
+    }
+
+    #[allow(unused)]
+    pub fn clear(&mut self) {
+        self.inner.ifg1.modify(|_, w| w.wdtifg().clear_bit());
+    }
+}
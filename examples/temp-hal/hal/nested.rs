@@ -0,0 +1,107 @@
+// Interrupt handlers that other interrupts can preempt. The MSP430 clears GIE on interrupt entry,
+// so normally a handler runs to completion before any other interrupt is taken, and a long one
+// (say, parsing a frame received over the UART) delays e.g. timer events. `nested_interrupt!`
+// splits such a handler in two:
+//
+//     nested_interrupt!(USCIAB0RX, receive, process);
+//
+//     // Runs with interrupts disabled. Acknowledge the source and grab what's needed.
+//     fn receive(cs: CriticalSection) -> u8 { ... }
+//
+//     // Runs with interrupts enabled, so other handlers can preempt it.
+//     fn process(byte: u8, nested: Nested) {
+//         nested.lock(|cs| unsafe { SHARED.with(cs, |s| ...) });
+//     }
+//
+// The first half gets a `CriticalSection`, and may return anything that doesn't borrow from it.
+// The second half gets a `Nested` token instead, so code in it can't assume it has exclusive
+// access: `Shared`, `mspint::Mutex` and the like need a `CriticalSection`, and `lock` only hands
+// one out with interrupts disabled again. Handlers defined with `#[interrupt]`,
+// `wake_interrupt!` or `app!` tasks still run with interrupts disabled throughout, so their
+// `CriticalSection` stays valid even when they preempt a nested handler. Neither `app!`
+// resources nor `Shared` values can be reached from the second half without locking.
+//
+// If the source can fire again before the second half is done, the handler nests within itself.
+// Disable the source's interrupt in the first half and re-enable it at the end of the second if
+// that's a problem. Every level of nesting takes stack, so leave some headroom.
+//
+// A `wake_interrupt!` handler that preempts the second half clears the low-power mode bits of the
+// status register it interrupted, which is the second half's and not main's. So that its wakeup
+// isn't lost, a nested handler always wakes main on return, like a `wake_interrupt!` handler
+// returning true. Use it together with `lpm::sleep_unless`, which rechecks for work anyway.
+
+use core::marker::PhantomData;
+
+use msp430::{interrupt as mspint, critical_section as mspcs};
+
+// Given to the second half of a nested handler in place of a `CriticalSection`.
+pub struct Nested {
+    // Only meaningful within the handler it was given to.
+    _not_send: PhantomData<*const ()>,
+}
+
+#[allow(unused)]
+impl Nested {
+    // Run `f` with interrupts disabled. Keep it short; it delays other interrupts again.
+    pub fn lock<R, F: FnOnce(mspint::CriticalSection) -> R>(&self, f: F) -> R {
+        mspcs::with(f)
+    }
+}
+
+// The body of the handler defined by `nested_interrupt!`.
+//
+// SAFETY: Must only be called on interrupt entry, before interrupts are re-enabled.
+#[allow(unused)]
+pub unsafe fn run<T>(ack: fn(mspint::CriticalSection) -> T, body: fn(T, Nested)) {
+    // The lifetime of the `CriticalSection` is specific to `ack`, so it can't end up in `T`.
+    let value = ack(mspint::CriticalSection::new());
+
+    mspint::enable();
+    body(value, Nested { _not_send: PhantomData });
+    // Nothing else to nest in while the registers are restored.
+    mspint::disable();
+}
+
+// Define the handler for an interrupt vector that re-enables interrupts after acknowledging its
+// source; see above. `$ack` is a `fn(CriticalSection) -> T` and `$body` a `fn(T, Nested)`, or
+// closures that coerce to them. Use instead of `#[interrupt]`, not together with it.
+#[macro_export]
+macro_rules! nested_interrupt {
+    ($vector:ident, $ack:expr, $body:expr) => {
+        const _: () = {
+            // Rejects names that aren't interrupt vectors of the device.
+            let _ = {{device}}::Interrupt::$vector;
+
+            #[export_name = concat!("__nested_", stringify!($vector))]
+            extern "C" fn handler() {
+                // Outside the `unsafe` block, so the caller's expressions don't end up in it.
+                let ack: fn(msp430::interrupt::CriticalSection) -> _ = $ack;
+                let body: fn(_, $crate::hal::nested::Nested) = $body;
+
+                // SAFETY: Only called by the stub below, on interrupt entry.
+                unsafe { $crate::hal::nested::run(ack, body) };
+            }
+        };
+
+        // As for `wake_interrupt!`, except that the mode bits are always cleared.
+        core::arch::global_asm!(concat!(
+            ".section .text.", stringify!($vector), ",\"ax\",@progbits\n",
+            ".global ", stringify!($vector), "\n",
+            ".balign 2\n",
+            stringify!($vector), ":\n",
+            "push r11\n",
+            "push r12\n",
+            "push r13\n",
+            "push r14\n",
+            "push r15\n",
+            "call #__nested_", stringify!($vector), "\n",
+            "bic #0xf0, 10(r1)\n",
+            "pop r15\n",
+            "pop r14\n",
+            "pop r13\n",
+            "pop r12\n",
+            "pop r11\n",
+            "reti\n",
+        ));
+    };
+}
@@ -0,0 +1,160 @@
+// State kept in RAM across resets that don't lose power, using the `.noinit` section from
+// memory.x, plus the reset cause needed to make sense of it.
+//
+// A `NoInit<T>` must be a static placed in `.noinit`:
+//
+//     #[link_section = ".noinit"]
+//     static STATE: NoInit<u16> = NoInit::new();
+//
+// Its initializer is never written to RAM; after power-on the contents are garbage. A magic
+// word and a checksum over the value tell the two apart. The checksum includes the size of `T`,
+// so that a firmware update changing the type also invalidates the old contents. `T` should not
+// contain padding, which isn't covered by the checksum.
+
+use core::cell::UnsafeCell;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use msp430::interrupt as mspint;
+
+const MAGIC: u16 = 0x4E49;
+
+#[repr(C)]
+struct Slot<T> {
+    magic: u16,
+    checksum: u16,
+    value: MaybeUninit<T>,
+}
+
+pub struct NoInit<T> {
+    slot: UnsafeCell<Slot<T>>,
+}
+
+// SAFETY: The slot is only accessed within critical sections.
+unsafe impl<T: Copy + Send> Sync for NoInit<T> {}
+
+#[allow(unused)]
+impl<T: Copy> NoInit<T> {
+    pub const fn new() -> Self {
+        NoInit {
+            slot: UnsafeCell::new(Slot {
+                magic: 0,
+                checksum: 0,
+                value: MaybeUninit::uninit(),
+            }),
+        }
+    }
+
+    // The value from before the last reset, if there is an intact one.
+    pub fn get(&self, _cs: mspint::CriticalSection) -> Option<T> {
+        let slot = self.slot.get();
+
+        // SAFETY: Within a critical section, nothing else accesses the slot. The RAM behind it
+        // may be garbage, but any bit pattern is a valid u16. The value is only read as T once
+        // the checksum shows it was written by `set`.
+        unsafe {
+            if ptr::read_volatile(ptr::addr_of!((*slot).magic)) != MAGIC
+                || ptr::read_volatile(ptr::addr_of!((*slot).checksum)) != checksum::<T>(slot)
+            {
+                return None;
+            }
+
+            Some((*slot).value.assume_init())
+        }
+    }
+
+    pub fn set(&self, _cs: mspint::CriticalSection, value: T) {
+        let slot = self.slot.get();
+
+        // SAFETY: As for `get`.
+        unsafe {
+            (*slot).value = MaybeUninit::new(value);
+            ptr::write_volatile(ptr::addr_of_mut!((*slot).checksum), checksum::<T>(slot));
+            ptr::write_volatile(ptr::addr_of_mut!((*slot).magic), MAGIC);
+        }
+    }
+
+    // The intact value from before the last reset, or `init` otherwise, which is also stored.
+    pub fn get_or_set(&self, cs: mspint::CriticalSection, init: T) -> T {
+        self.get(cs).unwrap_or_else(|| {
+            self.set(cs, init);
+            init
+        })
+    }
+
+    pub fn clear(&self, _cs: mspint::CriticalSection) {
+        // SAFETY: As for `get`.
+        unsafe { ptr::write_volatile(ptr::addr_of_mut!((*self.slot.get()).magic), 0) };
+    }
+}
+
+// Fletcher-16 over the bytes of the value, seeded with its size.
+//
+// SAFETY: `slot` must be valid for reads, and in `.noinit`. Its bytes are read whether or not
+// they were ever written, which is what distinguishes garbage from a stored value. That relies
+// on `.noinit` being NOLOAD RAM: a volatile read returns whatever the RAM holds, and every bit
+// pattern is a valid u8.
+unsafe fn checksum<T>(slot: *const Slot<T>) -> u16 {
+    let bytes = ptr::addr_of!((*slot).value) as *const MaybeUninit<u8>;
+    let (mut a, mut b) = (mem::size_of::<T>() as u16 % 255, 0u16);
+
+    for i in 0..mem::size_of::<T>() {
+        let byte = ptr::read_volatile(bytes.add(i)).assume_init();
+        a = (a + u16::from(byte)) % 255;
+        b = (b + a) % 255;
+    }
+
+    (b << 8) | a
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetCause {
+    // Power-on or brownout; RAM, including `.noinit`, is garbage.
+    PowerOn,
+    ResetPin,
+    // Watchdog timeout, or a write to WDTCTL without the password.
+    Watchdog,
+    // A software-initiated or otherwise unidentified PUC, e.g. a flash key violation.
+    Other,
+}
+
+// Handle to the PORIFG, RSTIFG and WDTIFG reset flags in IFG1.
+pub struct ResetFlags {
+    inner: {{device}}::SPECIAL_FUNCTION,
+}
+
+#[allow(unused)]
+impl ResetFlags {
+    pub(super) fn new(inner: {{device}}::SPECIAL_FUNCTION) -> Self {
+        ResetFlags { inner }
+    }
+
+    // Cause of the last reset, clearing the flags so the next reset is reported correctly.
+    // Must be called before the watchdog is used as an interval timer, which also sets WDTIFG.
+    pub fn take_cause(&mut self) -> ResetCause {
+        let ifg1 = self.inner.ifg1.read();
+
+        // A power-on sets PORIFG and clears RSTIFG. Check PORIFG first regardless, since if it's
+        // set, RAM is garbage whatever else happened since.
+        let cause = if ifg1.porifg().bit_is_set() {
+            ResetCause::PowerOn
+        } else if ifg1.rstifg().bit_is_set() {
+            ResetCause::ResetPin
+        } else if ifg1.wdtifg().bit_is_set() {
+            ResetCause::Watchdog
+        } else {
+            ResetCause::Other
+        };
+
+        self.inner.ifg1.modify(|_, w| {
+            w.porifg()
+                .clear_bit()
+                .rstifg()
+                .clear_bit()
+                .wdtifg()
+                .clear_bit()
+        });
+
+        cause
+    }
+}
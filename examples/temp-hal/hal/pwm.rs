@@ -0,0 +1,117 @@
+// PWM on TIMER1_A3 (TIMER0_A3 is used by `Timer`). TA1CCR0 sets the period; channels 1 and 2
+// use reset/set output mode, so the duty cycle is the number of high counts per period.
+//
+// Each channel can only be created from a pin that actually carries its output, which is
+// checked at compile time through the `Ta1Ch1Pin`/`Ta1Ch2Pin` traits.
+
+use super::gpio::{Alternate, Primary, P2_1, P2_2, P2_4, P2_5};
+
+pub trait Ta1Ch1Pin {
+    type Configured;
+
+    fn configure(self) -> Self::Configured;
+}
+
+pub trait Ta1Ch2Pin {
+    type Configured;
+
+    fn configure(self) -> Self::Configured;
+}
+
+macro_rules! pwm_pins {
+    ($($Trait:ident: $($PXi:ident),+;)+) => {
+        $($(
+            impl<MODE> $Trait for $PXi<MODE> {
+                type Configured = $PXi<Alternate<Primary>>;
+
+                fn configure(self) -> Self::Configured {
+                    self.into_alternate_primary_output()
+                }
+            }
+        )+)+
+    };
+}
+
+pwm_pins!(
+    Ta1Ch1Pin: P2_1, P2_2;
+    Ta1Ch2Pin: P2_4, P2_5;
+);
+
+pub struct Pwm {
+    inner: {{device}}::TIMER1_A3,
+}
+
+#[allow(unused)]
+impl Pwm {
+    // Counts SMCLK, so the PWM frequency is SMCLK / (period + 1).
+    pub fn new(inner: {{device}}::TIMER1_A3, period: u16) -> Self {
+        inner.taccr0.write(|w| w.bits(period));
+        inner
+            .tactl
+            .write(|w| w.tassel().tassel_2().mc().mc_1().taclr().set_bit());
+
+        Pwm { inner }
+    }
+
+    #[allow(unused)]
+    pub fn channel1<P: Ta1Ch1Pin>(&mut self, pin: P) -> PwmChannel1<P::Configured> {
+        self.inner.taccr1.write(|w| w.bits(0));
+        self.inner.tacctl1.write(|w| w.outmod().outmod_7());
+
+        PwmChannel1 {
+            _pin: pin.configure(),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn channel2<P: Ta1Ch2Pin>(&mut self, pin: P) -> PwmChannel2<P::Configured> {
+        self.inner.taccr2.write(|w| w.bits(0));
+        self.inner.tacctl2.write(|w| w.outmod().outmod_7());
+
+        PwmChannel2 {
+            _pin: pin.configure(),
+        }
+    }
+}
+
+// The PAC shares TIMER0_A3's register block, and its field names, with TIMER1_A3.
+fn timer() -> &'static {{device}}::timer0_a3::RegisterBlock {
+    // SAFETY: Channels only write their own TA1CCRx/TA1CCTLx, and can only be created from a
+    // `Pwm` that owns TIMER1_A3.
+    unsafe { &*{{device}}::TIMER1_A3::ptr() }
+}
+
+macro_rules! pwm_channel {
+    ($($PwmChannelX:ident: ($taxccrx:ident, $taxcctlx:ident),)+) => {
+        $(
+            pub struct $PwmChannelX<PIN> {
+                _pin: PIN,
+            }
+
+            #[allow(unused)]
+            impl<PIN> $PwmChannelX<PIN> {
+                pub fn max_duty(&self) -> u16 {
+                    timer().taccr0.read().bits()
+                }
+
+                pub fn set_duty(&mut self, duty: u16) {
+                    timer().$taxccrx.write(|w| w.bits(duty));
+                }
+
+                pub fn enable(&mut self) {
+                    timer().$taxcctlx.write(|w| w.outmod().outmod_7());
+                }
+
+                // Output mode 0 drives the pin from the OUT bit, which is kept low.
+                pub fn disable(&mut self) {
+                    timer().$taxcctlx.write(|w| w.outmod().outmod_0().out().clear_bit());
+                }
+            }
+        )+
+    };
+}
+
+pwm_channel!(
+    PwmChannel1: (taccr1, tacctl1),
+    PwmChannel2: (taccr2, tacctl2),
+);
@@ -0,0 +1,87 @@
+// A value shared between main and interrupt handlers that can be initialized before interrupts
+// are enabled, i.e. in the `pre_interrupt` hook of `#[entry(interrupt_enable(...))]`. Unlike
+// `Mutex<RefCell<Option<T>>>`, access needs no `Option` or borrow flag check, and so has no
+// panic paths either:
+//
+//     static TIMER: Shared<Timer> = Shared::uninit();
+//
+//     fn init(cs: CriticalSection) {
+//         // SAFETY: Interrupts are still disabled, and nothing has used TIMER yet.
+//         unsafe { TIMER.init(cs, timer) };
+//     }
+//
+//     // SAFETY: TIMER is initialized by `init`, and this isn't within another `TIMER.with`.
+//     unsafe { TIMER.with(cs, |timer| timer.wait()) }
+//
+// The checks `RefCell` and `Option` do at run time become the safety contract of `with`
+// instead: it must not be called before `init`, nor on a `Shared` from within its own closure.
+// Debug builds check both.
+// The `CriticalSection` takes care of the rest; the second half of a `nested_interrupt!` handler
+// only gets one by locking.
+//
+// The `timer-shared` example's docs list the difference in size against `timer`.
+
+use core::cell::UnsafeCell;
+#[cfg(debug_assertions)]
+use core::cell::Cell;
+use core::mem::MaybeUninit;
+
+use msp430::interrupt as mspint;
+
+#[cfg(debug_assertions)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Uninit,
+    Idle,
+    InUse,
+}
+
+pub struct Shared<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    #[cfg(debug_assertions)]
+    state: Cell<State>,
+}
+
+// SAFETY: The value is only accessed within critical sections, following the rules above.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+#[allow(unused)]
+impl<T> Shared<T> {
+    pub const fn uninit() -> Self {
+        Shared {
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            #[cfg(debug_assertions)]
+            state: Cell::new(State::Uninit),
+        }
+    }
+
+    // SAFETY: Must be called once, before interrupts are enabled and before any call to `with`.
+    pub unsafe fn init(&self, _cs: mspint::CriticalSection, value: T) {
+        #[cfg(debug_assertions)]
+        {
+            assert!(self.state.get() == State::Uninit);
+            self.state.set(State::Idle);
+        }
+
+        (*self.value.get()).write(value);
+    }
+
+    // SAFETY: `init` must have been called, and this must not be called from within the closure
+    // of another `with` on the same `Shared`, which would hand out a second `&mut T`.
+    pub unsafe fn with<R, F: FnOnce(&mut T) -> R>(&self, _cs: mspint::CriticalSection, f: F) -> R {
+        #[cfg(debug_assertions)]
+        {
+            assert!(self.state.get() == State::Idle);
+            self.state.set(State::InUse);
+        }
+
+        // SAFETY: Initialized and not in use, per the contract. Within a critical section,
+        // nothing else runs, so the closure has the only reference.
+        let result = f((*self.value.get()).assume_init_mut());
+
+        #[cfg(debug_assertions)]
+        self.state.set(State::Idle);
+
+        result
+    }
+}
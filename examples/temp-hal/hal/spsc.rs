@@ -0,0 +1,206 @@
+// Fixed-capacity single-producer single-consumer queue, for passing bytes, samples or events
+// from an interrupt handler to main (or the other way around) without disabling interrupts.
+//
+// The MSP430 has no compare-and-swap, but aligned 16-bit loads and stores are single
+// instructions, which interrupts can't split. That is enough here: the producer is the only one
+// to store `tail`, and the consumer the only one to store `head`. Each writes or reads a slot
+// before publishing its new index, so the other side never sees a slot before it's ready:
+//
+//     static SAMPLES: Queue<u16, 16> = Queue::new();
+//
+//     // SAFETY: The only call, so there is one producer and one consumer.
+//     let (tx, rx) = unsafe { SAMPLES.split_unchecked() };
+//
+//     // Interrupt handler                  // Main
+//     if tx.push(sample).is_err() {         while let Some(sample) = rx.pop() {
+//         // Full, drop it.                     process(sample);
+//     }                                     }
+//
+// Only uses `core`, so the same file is stress tested on the host by `tools/spsc-stress`. There,
+// the indices are `AtomicU16`s, so the test checks the algorithm rather than the MSP430 build.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::Ordering;
+#[cfg(target_arch = "msp430")]
+use core::{ptr, sync::atomic::compiler_fence};
+
+// A queue index. `core` has no atomics for the MSP430 (its maximum atomic width is 0), so
+// volatile accesses keep the compiler from splitting, merging or dropping loads and stores,
+// and fences keep slot accesses from moving across them. With a single core, that's all the
+// ordering needed.
+#[cfg(target_arch = "msp430")]
+#[repr(transparent)]
+struct Index(UnsafeCell<u16>);
+
+#[cfg(target_arch = "msp430")]
+impl Index {
+    const fn new() -> Self {
+        Index(UnsafeCell::new(0))
+    }
+
+    fn load(&self, order: Ordering) -> u16 {
+        // SAFETY: Aligned, and a single instruction, so never torn.
+        let value = unsafe { ptr::read_volatile(self.0.get()) };
+        if order == Ordering::Acquire {
+            compiler_fence(Ordering::Acquire);
+        }
+        value
+    }
+
+    fn store(&self, value: u16, order: Ordering) {
+        if order == Ordering::Release {
+            compiler_fence(Ordering::Release);
+        }
+        // SAFETY: As for `load`; only one side ever stores to an index.
+        unsafe { ptr::write_volatile(self.0.get(), value) };
+    }
+}
+
+#[cfg(not(target_arch = "msp430"))]
+#[repr(transparent)]
+struct Index(core::sync::atomic::AtomicU16);
+
+#[cfg(not(target_arch = "msp430"))]
+impl Index {
+    const fn new() -> Self {
+        Index(core::sync::atomic::AtomicU16::new(0))
+    }
+
+    fn load(&self, order: Ordering) -> u16 {
+        self.0.load(order)
+    }
+
+    fn store(&self, value: u16, order: Ordering) {
+        self.0.store(value, order)
+    }
+}
+
+pub struct Queue<T, const N: usize> {
+    buf: UnsafeCell<MaybeUninit<[T; N]>>,
+    // Free-running, wrapping indices; the slot is the index modulo `N`. `tail - head` is the
+    // length, which is unambiguous because `N` divides 2^16 and is at most half of it.
+    head: Index,
+    tail: Index,
+}
+
+// SAFETY: Slots are handed from the producer to the consumer through the indices, see above.
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+#[allow(unused)]
+impl<T, const N: usize> Queue<T, N> {
+    const CAPACITY_OK: () = assert!(
+        N.is_power_of_two() && N <= 0x8000,
+        "capacity must be a power of two, at most 32768"
+    );
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::CAPACITY_OK;
+
+        Queue {
+            buf: UnsafeCell::new(MaybeUninit::uninit()),
+            head: Index::new(),
+            tail: Index::new(),
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        // SAFETY: `&mut self` rules out any other producer or consumer while these exist.
+        unsafe { self.split_unchecked() }
+    }
+
+    // Split a queue that can't be borrowed mutably, like one in a `static`.
+    //
+    // SAFETY: At most one `Producer` and one `Consumer` of this queue may exist at a time.
+    pub unsafe fn split_unchecked(&self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+
+    fn slot(&self, index: u16) -> *mut T {
+        let i = usize::from(index) & (N - 1);
+        // SAFETY: In bounds of the buffer.
+        unsafe { (self.buf.get() as *mut T).add(i) }
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        let (_, mut rx) = self.split();
+        while rx.pop().is_some() {}
+    }
+}
+
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+#[allow(unused)]
+impl<'a, T, const N: usize> Producer<'a, T, N> {
+    // Hands the value back if the queue is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let q = self.queue;
+        let tail = q.tail.load(Ordering::Relaxed);
+        // Acquire: the consumer is done reading the slot before it frees it.
+        let head = q.head.load(Ordering::Acquire);
+
+        if usize::from(tail.wrapping_sub(head)) == N {
+            return Err(value);
+        }
+
+        // SAFETY: The slot is free, and only this producer writes to free slots.
+        unsafe { q.slot(tail).write(value) };
+        // Release: publishes the slot to the consumer.
+        q.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    // The consumer may have popped more since.
+    pub fn len(&self) -> usize {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        usize::from(tail.wrapping_sub(self.queue.head.load(Ordering::Acquire)))
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+}
+
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+#[allow(unused)]
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    pub fn pop(&mut self) -> Option<T> {
+        let q = self.queue;
+        let head = q.head.load(Ordering::Relaxed);
+        // Acquire: the producer is done writing the slot before it publishes it.
+        let tail = q.tail.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        // SAFETY: The slot was published by the producer, which won't touch it again until
+        // it's freed below.
+        let value = unsafe { q.slot(head).read() };
+        // Release: frees the slot for the producer.
+        q.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    // The producer may have pushed more since.
+    pub fn len(&self) -> usize {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        usize::from(self.queue.tail.load(Ordering::Acquire).wrapping_sub(head))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
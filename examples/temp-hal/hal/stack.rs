@@ -0,0 +1,100 @@
+// Stack usage measurement by painting. The stack grows down from `_stack_start` (the end of RAM)
+// towards the end of the static data, `_enoinit`; nothing stops it from growing further and
+// silently corrupting `.noinit`, `.data` and `.bss`, in that order.
+//
+// `paint` fills the free RAM in between with a pattern at startup. The deepest the stack has
+// been is then wherever the pattern stops. The lowest few words of free RAM serve as a canary:
+// once those are gone, statics are about to be overwritten, if they aren't already.
+//
+// Call `paint` from the application's pre-init hook, before anything else touches the stack:
+//
+//     #[pre_init]
+//     unsafe fn pre_init() {
+//         hal::stack::paint();
+//     }
+
+use core::ptr;
+
+use msp430::interrupt as mspint;
+
+use super::noinit::NoInit;
+
+const PAINT: u16 = 0x5AA5;
+const CANARY_WORDS: usize = 4;
+// Stack used by `paint` itself beyond the address of its local, with room to spare.
+const PAINT_MARGIN: usize = 16;
+
+extern "C" {
+    static _enoinit: u16;
+    static _stack_start: u16;
+}
+
+// Set when the canary triggers a reset. How deep the stack went isn't known by then, only that
+// it reached the canary.
+#[link_section = ".noinit"]
+static OVERFLOW: NoInit<bool> = NoInit::new();
+
+fn bottom() -> usize {
+    ptr::addr_of!(_enoinit) as usize
+}
+
+fn top() -> usize {
+    ptr::addr_of!(_stack_start) as usize
+}
+
+// SAFETY: Must be called with interrupts disabled and as little as possible on the stack, i.e.
+// from `#[pre_init]`.
+pub unsafe fn paint() {
+    let marker = 0u16;
+    let sp = ptr::addr_of!(marker) as usize - PAINT_MARGIN;
+
+    let mut addr = bottom();
+    while addr < sp {
+        ptr::write_volatile(addr as *mut u16, PAINT);
+        addr += 2;
+    }
+}
+
+// Bytes of RAM available to the stack.
+pub fn size() -> usize {
+    top() - bottom()
+}
+
+// Peak stack usage in bytes since `paint`.
+pub fn high_water_mark() -> usize {
+    let mut addr = bottom();
+
+    // SAFETY: Everything between the end of the statics and the top of the stack is RAM.
+    while addr < top() && unsafe { ptr::read_volatile(addr as *const u16) } == PAINT {
+        addr += 2;
+    }
+
+    top() - addr
+}
+
+pub fn canary_intact() -> bool {
+    (0..CANARY_WORDS).all(|i| {
+        // SAFETY: As for `high_water_mark`.
+        unsafe { ptr::read_volatile((bottom() + i * 2) as *const u16) == PAINT }
+    })
+}
+
+// Reset the part if the canary is gone, recording that for `take_overflow`. Call periodically,
+// e.g. from the main loop.
+pub fn check_canary(cs: mspint::CriticalSection) {
+    if canary_intact() {
+        return;
+    }
+
+    OVERFLOW.set(cs, true);
+
+    // SAFETY: Deliberately writing WDTCTL without the password, which resets the part.
+    unsafe { ptr::write_volatile(0x0120 as *mut u16, 0) };
+}
+
+// Whether the canary caused the last reset.
+pub fn take_overflow(cs: mspint::CriticalSection) -> bool {
+    let overflow = OVERFLOW.get(cs) == Some(true);
+    OVERFLOW.clear(cs);
+    overflow
+}
@@ -0,0 +1,102 @@
+// Supply voltage monitoring through ADC10 channel 11, (Vcc - Vss) / 2.
+//
+// Vcc/2 is measured against the 1.5V reference, which covers Vcc up to 3V, the range where
+// a battery is getting low. Above that the conversion saturates and is repeated against the
+// 2.5V reference. The 2.5V reference itself needs Vcc >= 2.9V, which is why it isn't used
+// first. Results are only as accurate as the reference, about +/-1%, since the factory gain
+// and offset calibration isn't applied.
+
+use msp430::interrupt as mspint;
+
+use super::adc::{Adc10, HalfVcc, Reference, SampleTime};
+
+// Conversions at or above this are taken as saturated with the 1.5V reference.
+const SATURATED: u16 = 1020;
+// Hysteresis around the threshold, so a supply sagging under load doesn't flap.
+const HYSTERESIS_MV: u16 = 50;
+
+// Raw Vcc/2 conversion to Vcc in millivolts.
+fn to_millivolts(raw: u16, reference: Reference) -> u16 {
+    let vref_mv = match reference {
+        Reference::Internal2_5V => 2500,
+        _ => 1500,
+    };
+
+    (u32::from(raw) * 2 * vref_mv / 1023) as u16
+}
+
+// Blocking measurement of Vcc in millivolts. Leaves `adc` configured for whichever reference
+// was used last.
+pub fn supply_voltage(adc: &mut Adc10) -> u16 {
+    let mut half_vcc = HalfVcc;
+
+    adc.configure_settled(Reference::Internal1_5V, SampleTime::Cycles64);
+    let raw = adc.read(&mut half_vcc);
+    if raw < SATURATED {
+        return to_millivolts(raw, Reference::Internal1_5V);
+    }
+
+    adc.configure_settled(Reference::Internal2_5V, SampleTime::Cycles64);
+    let raw = adc.read(&mut half_vcc);
+    to_millivolts(raw, Reference::Internal2_5V)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SupplyEvent {
+    // Vcc in millivolts when the event was detected.
+    Low(u16),
+    Recovered(u16),
+}
+
+// Low-battery detection against a threshold. Measurements can be polled (`poll`), or taken in
+// the background with the result delivered in the ADC10 interrupt (`start` and
+// `on_interrupt`). Either way, an event is only returned when the state changes.
+pub struct SupplyMonitor {
+    threshold_mv: u16,
+    low: bool,
+}
+
+#[allow(unused)]
+impl SupplyMonitor {
+    pub fn new(threshold_mv: u16) -> Self {
+        SupplyMonitor {
+            threshold_mv,
+            low: false,
+        }
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+
+    pub fn poll(&mut self, adc: &mut Adc10) -> Option<SupplyEvent> {
+        let mv = supply_voltage(adc);
+        self.update(mv)
+    }
+
+    // Start a background measurement. Only the 1.5V reference is used, so a healthy supply
+    // above 3V reads as 3V, which is still above any sensible threshold.
+    pub fn start(&self, adc: &mut Adc10) {
+        adc.configure_settled(Reference::Internal1_5V, SampleTime::Cycles64);
+        adc.start_single(&mut HalfVcc, true);
+    }
+
+    // Call from the `ADC10` interrupt handler after `start`.
+    pub fn on_interrupt(&mut self, cs: mspint::CriticalSection) -> Option<SupplyEvent> {
+        let raw = Adc10::take_result(cs)?;
+        self.update(to_millivolts(raw, Reference::Internal1_5V))
+    }
+
+    // Feed in a measurement taken some other way.
+    pub fn update(&mut self, mv: u16) -> Option<SupplyEvent> {
+        if !self.low && mv < self.threshold_mv {
+            self.low = true;
+            Some(SupplyEvent::Low(mv))
+        } else if self.low && mv >= self.threshold_mv.saturating_add(HYSTERESIS_MV) {
+            self.low = false;
+            Some(SupplyEvent::Recovered(mv))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,68 @@
+// On-chip temperature sensor (ADC10 channel 10), read against the internal 1.5V reference.
+//
+// The sensor needs a sample time of at least 30us, more than 64 cycles of the undivided ADC10OSC,
+// so ADC10CLK is divided by 4 for its conversions. Readings use the per-device factory
+// calibration (CAL_ADC_15T30/CAL_ADC_15T85) when segment A still holds it, and the datasheet's
+// typical transfer function otherwise, which can be off by several degrees.
+
+use fixed::types::I8F8;
+
+use super::adc::{Adc10, ClockDivider, Reference, SampleTime, Temperature};
+use super::tlv::{self, TempCalibration};
+
+// Typical transfer function with the 1.5V reference: degC = (raw - 673) * 423 / 1024.
+const TYPICAL_OFFSET: i32 = 673;
+const TYPICAL_SLOPE: i32 = 423;
+
+pub struct InternalTemperature {
+    sensor: Temperature,
+    cal: Option<TempCalibration>,
+}
+
+#[allow(unused)]
+impl InternalTemperature {
+    pub fn new() -> Self {
+        InternalTemperature {
+            sensor: Temperature,
+            cal: tlv::temp_calibration(),
+        }
+    }
+
+    // Whether the factory calibration was found.
+    pub fn is_calibrated(&self) -> bool {
+        self.cal.is_some()
+    }
+
+    // Leaves `adc` configured for the temperature sensor; reconfigure it afterwards if it is
+    // shared with other channels.
+    pub fn read(&mut self, adc: &mut Adc10) -> I8F8 {
+        // The sensor itself turns on when selected, and settles within the sample time.
+        adc.configure_settled(Reference::Internal1_5V, SampleTime::Cycles64);
+        adc.set_clock_divider(ClockDivider::By4);
+
+        let raw = adc.read(&mut self.sensor);
+        I8F8::from_bits(self.to_bits(raw))
+    }
+
+    // Raw conversion result to degrees C with 8 fractional bits, saturated to the range of I8F8.
+    fn to_bits(&self, raw: u16) -> i16 {
+        let raw = i32::from(raw);
+
+        let bits = match self.cal {
+            // Linear between the two calibration points.
+            Some(TempCalibration { t30, t85 }) => {
+                let (t30, t85) = (i32::from(t30), i32::from(t85));
+                (raw - t30) * (85 - 30) * 256 / (t85 - t30) + 30 * 256
+            }
+            None => (raw - TYPICAL_OFFSET) * TYPICAL_SLOPE / 4,
+        };
+
+        bits.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16
+    }
+}
+
+impl Default for InternalTemperature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,89 @@
+// Factory calibration constants stored in the TLV structure of info segment A. Addresses are
+// those of the msp430g2553 (and the rest of the G2xx3 family). Erased (0xFF) entries mean the
+// constant isn't present, e.g. because segment A was erased.
+
+use core::ptr;
+
+const CALDCO_16MHZ: *const u8 = 0x10F8 as *const u8;
+const CALBC1_16MHZ: *const u8 = 0x10F9 as *const u8;
+const CALDCO_12MHZ: *const u8 = 0x10FA as *const u8;
+const CALBC1_12MHZ: *const u8 = 0x10FB as *const u8;
+const CALDCO_8MHZ: *const u8 = 0x10FC as *const u8;
+const CALBC1_8MHZ: *const u8 = 0x10FD as *const u8;
+const CALDCO_1MHZ: *const u8 = 0x10FE as *const u8;
+const CALBC1_1MHZ: *const u8 = 0x10FF as *const u8;
+
+#[derive(Clone, Copy, Debug)]
+#[allow(unused)]
+pub enum DcoFrequency {
+    Mhz1,
+    Mhz8,
+    Mhz12,
+    Mhz16,
+}
+
+impl DcoFrequency {
+    pub fn hz(self) -> u32 {
+        match self {
+            DcoFrequency::Mhz1 => 1_000_000,
+            DcoFrequency::Mhz8 => 8_000_000,
+            DcoFrequency::Mhz12 => 12_000_000,
+            DcoFrequency::Mhz16 => 16_000_000,
+        }
+    }
+}
+
+// Register values for BCSCTL1 and DCOCTL.
+#[derive(Clone, Copy, Debug)]
+pub struct DcoCalibration {
+    pub bcsctl1: u8,
+    pub dcoctl: u8,
+}
+
+pub fn dco_calibration(freq: DcoFrequency) -> Option<DcoCalibration> {
+    let (bc1, dco) = match freq {
+        DcoFrequency::Mhz1 => (CALBC1_1MHZ, CALDCO_1MHZ),
+        DcoFrequency::Mhz8 => (CALBC1_8MHZ, CALDCO_8MHZ),
+        DcoFrequency::Mhz12 => (CALBC1_12MHZ, CALDCO_12MHZ),
+        DcoFrequency::Mhz16 => (CALBC1_16MHZ, CALDCO_16MHZ),
+    };
+
+    // SAFETY: Info memory is always mapped and readable.
+    let (bcsctl1, dcoctl) = unsafe { (ptr::read_volatile(bc1), ptr::read_volatile(dco)) };
+
+    if bcsctl1 == 0xFF && dcoctl == 0xFF {
+        None
+    } else {
+        Some(DcoCalibration { bcsctl1, dcoctl })
+    }
+}
+
+// ADC10 calibration block, only present if its tag byte is.
+const TAG_ADC10_1: *const u8 = 0x10DA as *const u8;
+const TAG_ADC10_1_VALUE: u8 = 0x10;
+const CAL_ADC_15T30: *const u16 = 0x10E2 as *const u16;
+const CAL_ADC_15T85: *const u16 = 0x10E4 as *const u16;
+
+// Temperature sensor readings at 30 and 85 degrees C with the 1.5V reference.
+#[derive(Clone, Copy, Debug)]
+pub struct TempCalibration {
+    pub t30: u16,
+    pub t85: u16,
+}
+
+pub fn temp_calibration() -> Option<TempCalibration> {
+    // SAFETY: Info memory is always mapped and readable.
+    let (tag, t30, t85) = unsafe {
+        (
+            ptr::read_volatile(TAG_ADC10_1),
+            ptr::read_volatile(CAL_ADC_15T30),
+            ptr::read_volatile(CAL_ADC_15T85),
+        )
+    };
+
+    if tag != TAG_ADC10_1_VALUE || t30 == 0xFFFF || t85 == 0xFFFF || t85 <= t30 {
+        None
+    } else {
+        Some(TempCalibration { t30, t85 })
+    }
+}
@@ -1,148 +1,214 @@
 //! Temperature sensor demo for the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2)
 //! development kit. Make sure jumpers are set to HW UART, (possibly) disconnect the green LED
 //! jumper, and attach a [TCN75A](https://www.microchip.com/en-us/product/TCN75A) to pins 1.6
-//! (SCK) and 1.7 (SDA). Push the button attached to 1.3 to toggle between F, and C!
+//! (SCK) and 1.7 (SDA). Click the button attached to 1.3 to toggle between F, and C! The choice
+//! is remembered across power cycles. Hold the button down to print peak stack usage. In
+//! between temperature readings and button presses, the part sleeps in LPM3.
 //!
 //! ---
 
 #![no_main]
 #![no_std]
 #![feature(abi_msp430_interrupt)]
+#![feature(asm_experimental_arch)]
 
 mod hal;
+use hal::button::{Button, ButtonConfig, ButtonEvent, EventConsumer, EventQueue};
+use hal::flash::Flash;
+use hal::gpio::GpioExt;
+use hal::kvstore::KvStore;
+use hal::lpm::{self, LowPowerMode};
+use hal::shared::Shared;
 use hal::*;
 
 mod newtypes;
 
 extern crate panic_msp430;
 
-use core::cell::{Cell, RefCell};
+use core::cell::Cell;
 use core::fmt::Write;
 
+use embedded_hal::digital::blocking::OutputPin;
 use embedded_hal::serial::{self, nb::Write as SerWrite};
 use fixed::traits::LossyFrom;
 use fixed::types::{I8F8, I9F7};
 use fixed_macro::types::{I8F8, I9F7};
 use msp430::{interrupt as mspint, critical_section as mspcs};
-use msp430_rt::entry;
+use msp430_rt::{entry, pre_init};
 use {{device}}::{interrupt, Peripherals};
-use once_cell::unsync::OnceCell;
 use tcn75a::{ConfigReg, Resolution, Tcn75a};
 
 // Serial in the future may use interrupts. TCN75A is currently blocking and does not use
 // interrupts, thus is not static.
-static TIMER: mspint::Mutex<RefCell<Option<Timer>>> = mspint::Mutex::new(RefCell::new(None));
-static SERIAL: mspint::Mutex<RefCell<Option<Serial>>> = mspint::Mutex::new(RefCell::new(None));
-static PORT1_PINS: mspint::Mutex<OnceCell<{{device}}::PORT_1_2>> =
-    mspint::Mutex::new(OnceCell::new());
+static TIMER: Shared<Timer> = Shared::uninit();
+static SERIAL: Shared<Serial> = Shared::uninit();
+static PORT1_INT: Shared<gpio::Port1Interrupts> = Shared::uninit();
+static BUTTON: Shared<Button<ButtonPin>> = Shared::uninit();
+// Pushed to by the button's interrupt handlers, popped by main without a critical section.
+static BUTTON_EVENTS: EventQueue = EventQueue::new();
 static TEMP_DISPLAY: mspint::Mutex<Cell<TempDisplay>> =
     mspint::Mutex::new(Cell::new(TempDisplay::Celsius));
 
+type ButtonPin = gpio::P1_3<gpio::Input<gpio::PullUp>>;
+
+// Key of the persisted TempDisplay in the settings store.
+const KEY_TEMP_DISPLAY: u8 = 0;
+
 #[derive(Debug, Clone, Copy)]
 enum TempDisplay {
     Celsius,
     Fahrenheit,
 }
 
-fn init(cs: mspint::CriticalSection) -> Tcn75a<I2c> {
+#[pre_init]
+unsafe fn pre_init() {
+    stack::paint();
+}
+
+fn init(cs: mspint::CriticalSection) -> (Tcn75a<I2c>, KvStore, EventConsumer) {
     let p = Peripherals::take().unwrap();
 
-    WatchdogTimer::new(p.WATCHDOG_TIMER).disable().unwrap();
-
-    let clock = &p.SYSTEM_CLOCK; // Default clk is around 1.1 MHz using DCO. Submain clock also fed by it.
-    clock.bcsctl3.modify(|_, w| w.lfxt1s().lfxt1s_2()); // Use internal VLO for AUX clock (12kHz).
-    clock.bcsctl1.modify(|_, w| w.diva().diva_1()); // Divide AUX clock by two (6000 Hz).
-
-    let port_1_2 = &p.PORT_1_2;
-    port_1_2
-        .p1dir
-        .modify(|_, w| w.p0().set_bit().p3().clear_bit());
-    port_1_2
-        .p1out
-        .modify(|_, w| w.p0().set_bit().p3().set_bit()); // Pullup on P3.
-    port_1_2.p1ren.modify(|_, w| w.p3().set_bit());
-
-    // Set bits for UART and I2C operation.
-    port_1_2.p1sel.modify(|_, w| {
-        w.p1()
-            .set_bit()
-            .p2()
-            .set_bit()
-            .p6()
-            .set_bit()
-            .p7()
-            .set_bit()
-    });
-    port_1_2.p1sel2.modify(|_, w| {
-        w.p1()
-            .set_bit()
-            .p2()
-            .set_bit()
-            .p6()
-            .set_bit()
-            .p7()
-            .set_bit()
-    });
-
-    // Set bit to interrupt on button on P1.3
-    port_1_2.p1ie.modify(|_, w| w.p3().set_bit());
+    let wdt = WatchdogTimer::new(p.WATCHDOG_TIMER).disable().unwrap();
+
+    let mut sfr = SfrIfg::new(p.SPECIAL_FUNCTION);
+
+    // Calibrated 1 MHz DCO, which also feeds the submain clock.
+    let mut clocks = clock::ClockConfig::new(p.SYSTEM_CLOCK)
+        .dco_calibrated(tlv::DcoFrequency::Mhz1)
+        .aclk_vlo() // Use internal VLO for AUX clock (nominally 12kHz).
+        .aclk_divider(clock::AclkDivider::By2) // Divide AUX clock by two (nominally 6000 Hz).
+        .freeze(&mut sfr.osc_fault);
+    clocks.calibrate_vlo(&p.TIMER0_A3); // The VLO varies a lot between parts; measure it.
+
+    // Settings live in info memory, and survive power cycles.
+    let settings = KvStore::mount(Flash::new(p.FLASH, &clocks).unwrap()).unwrap();
+    let mut buf = [0; 1];
+    if let Some(1) = settings.get(KEY_TEMP_DISPLAY, &mut buf) {
+        let temp_display = match buf[0] {
+            1 => TempDisplay::Fahrenheit,
+            _ => TempDisplay::Celsius,
+        };
+        TEMP_DISPLAY.borrow(cs).set(temp_display);
+    }
+
+    let pins = p.PORT_1_2.split();
+    let mut led = pins.p1_0.into_push_pull_output();
+    led.set_high().unwrap();
+
+    // Button on P1.3 is debounced using the watchdog as an interval timer (ACLK/64, ~10ms).
+    // The pin interrupt on press only wakes the debouncer up.
+    let mut button_pin = pins.p1_3.into_pull_up_input();
+    button_pin.enable_interrupt(gpio::Edge::Falling);
+    let tick_ms = (64 * 1000 / clocks.aclk()) as u16;
+    // SAFETY: The only call, so there is one producer and one consumer.
+    let (events_tx, events_rx) = unsafe { BUTTON_EVENTS.split_unchecked() };
+    let config = ButtonConfig::from_tick_ms(tick_ms);
+    let button = Button::new(button_pin, true, config, events_tx);
+    wdt.start_interval(WatchdogDivider::By64).unwrap();
+    sfr.wdtie.enable();
 
     let mut timer = Timer::new(p.TIMER0_A3);
-    timer.start(6000u16).unwrap();
+    timer.start_ms(&clocks, 1000).unwrap();
 
-    let serial = Serial::new(p.USCI_A0_UART_MODE);
+    let mut serial = Serial::new(p.USCI_A0_UART_MODE, pins.p1_2, pins.p1_1, &clocks);
+    if stack::take_overflow(cs) {
+        let s: &mut dyn SerWrite<Error = serial::ErrorKind> = &mut serial;
+        writeln!(s, "stack overflow reset, {} bytes of stack", stack::size()).unwrap();
+    }
 
-    let i2c_flags = SfrIfg::new(p.SPECIAL_FUNCTION);
-    let i2c = I2c::new(p.USCI_B0_I2C_MODE, i2c_flags.ucb0ifg);
+    let i2c = I2c::new(p.USCI_B0_I2C_MODE, pins.p1_6, pins.p1_7, sfr.ucb0ifg, &clocks);
 
     let mut tcn = Tcn75a::new(i2c, 0x48);
     let mut cfg = ConfigReg::new();
     cfg.set_resolution(Resolution::Bits12);
     tcn.set_config_reg(cfg).unwrap();
 
-    *TIMER.borrow(cs).borrow_mut() = Some(timer);
-    *SERIAL.borrow(cs).borrow_mut() = Some(serial);
-    PORT1_PINS.borrow(cs).set(p.PORT_1_2).ok().unwrap();
+    // SAFETY: Interrupts are still disabled, and each is only initialized here.
+    unsafe {
+        TIMER.init(cs, timer);
+        SERIAL.init(cs, serial);
+        PORT1_INT.init(cs, pins.int1);
+        BUTTON.init(cs, button);
+    }
 
-    tcn
+    (tcn, settings, events_rx)
 }
 
 #[entry(interrupt_enable(pre_interrupt = init))]
-fn main(mut tcn: Tcn75a<I2c>) -> ! {
+fn main(periph: (Tcn75a<I2c>, KvStore, EventConsumer)) -> ! {
+    let (mut tcn, mut settings, mut events) = periph;
+
     loop {
+        // ACLK keeps running in LPM3, and with it the timer and the watchdog debouncing the
+        // button.
+        lpm::sleep_unless(LowPowerMode::Lpm3, |cs| {
+            // SAFETY: The statics are all initialized in `init`, before interrupts are enabled,
+            // and none is used from within its own `with`.
+            let elapsed = unsafe { TIMER.with(cs, |t| t.is_elapsed()) };
+            elapsed || !events.is_empty()
+        });
+
         mspcs::with(|cs| {
-            let mut t_ref = TIMER.borrow(cs).borrow_mut();
-            let mut s_ref = SERIAL.borrow(cs).borrow_mut();
-
-            match t_ref.as_mut().unwrap().wait() {
-                Ok(()) => {
-                    let tmp_result = tcn.temperature();
-
-                    // Avoid bringing in formatting for panic due to optimization
-                    // issues.
-                    let tmp: I8F8 = match tmp_result {
-                        Ok(t) => t.into(),
-                        Err(_) => {
-                            I8F8!(0)
+            stack::check_canary(cs);
+
+            // SAFETY: As above.
+            unsafe {
+                SERIAL.with(cs, |ser| {
+                    let s: &mut dyn SerWrite<Error = serial::ErrorKind> = ser;
+
+                    while let Some(ev) = events.pop() {
+                        if ev == ButtonEvent::LongPress {
+                            writeln!(
+                                s,
+                                "stack: {}/{} bytes",
+                                stack::high_water_mark(),
+                                stack::size()
+                            )
+                            .unwrap();
+                        } else if ev == ButtonEvent::Click {
+                            let temp_display = match TEMP_DISPLAY.borrow(cs).get() {
+                                TempDisplay::Celsius => TempDisplay::Fahrenheit,
+                                TempDisplay::Fahrenheit => TempDisplay::Celsius,
+                            };
+
+                            TEMP_DISPLAY.borrow(cs).set(temp_display);
+                            // Not worth crashing over if it fails; the choice just won't persist.
+                            let _ = settings.set(KEY_TEMP_DISPLAY, &[temp_display as u8]);
                         }
-                    };
-
-                    let s: &mut dyn SerWrite<Error = serial::ErrorKind> = s_ref.as_mut().unwrap();
+                    }
 
-                    match TEMP_DISPLAY.borrow(cs).get() {
-                        TempDisplay::Celsius => {
-                            let tmp_c: newtypes::fmt::I8F8SmallFmt = tmp.into();
-                            write!(s, "{} C\n", tmp_c).unwrap()
-                        }
-                        TempDisplay::Fahrenheit => {
-                            // Don't bring in FixedI32 formatting.
-                            let tmp_f: newtypes::fmt::I9F7SmallFmt =
-                                (I9F7!(1.8) * I9F7::lossy_from(tmp) + I9F7!(32)).into();
-                            write!(s, "{} F\n", tmp_f).unwrap()
+                    match TIMER.with(cs, |t| t.wait()) {
+                        Ok(()) => {
+                            let tmp_result = tcn.temperature();
+
+                            // Avoid bringing in formatting for panic due to optimization
+                            // issues.
+                            let tmp: I8F8 = match tmp_result {
+                                Ok(t) => t.into(),
+                                Err(_) => {
+                                    I8F8!(0)
+                                }
+                            };
+
+                            match TEMP_DISPLAY.borrow(cs).get() {
+                                TempDisplay::Celsius => {
+                                    let tmp_c: newtypes::fmt::I8F8SmallFmt = tmp.into();
+                                    write!(s, "{} C\n", tmp_c).unwrap()
+                                }
+                                TempDisplay::Fahrenheit => {
+                                    // Don't bring in FixedI32 formatting.
+                                    let tmp_f: newtypes::fmt::I9F7SmallFmt =
+                                        (I9F7!(1.8) * I9F7::lossy_from(tmp) + I9F7!(32)).into();
+                                    write!(s, "{} F\n", tmp_f).unwrap()
+                                }
+                            }
                         }
+                        _ => {}
                     }
-                }
-                _ => {}
+
+                    // The UART runs off SMCLK, which is stopped in LPM3.
+                    nb::block!(s.flush()).unwrap();
+                })
             }
         })
     }
@@ -150,29 +216,39 @@ fn main(mut tcn: Tcn75a<I2c>) -> ! {
 
 #[interrupt]
 fn PORT1(cs: CriticalSection) {
-    let p = PORT1_PINS.borrow(cs).get().unwrap();
-    let mut temp_display = TEMP_DISPLAY.borrow(cs).get();
+    // SAFETY: As in `main`.
+    let pending = unsafe { PORT1_INT.with(cs, |int| int.take_pending()) };
 
-    temp_display = match temp_display {
-        TempDisplay::Celsius => TempDisplay::Fahrenheit,
-        TempDisplay::Fahrenheit => TempDisplay::Celsius,
-    };
+    if pending.contains(3) {
+        // SAFETY: As in `main`.
+        unsafe { BUTTON.with(cs, |b| b.on_edge()) };
+    }
+}
 
-    TEMP_DISPLAY.borrow(cs).set(temp_display);
+wake_interrupt!(WDT, wdt_tick);
 
-    p.p1ifg.modify(|_, w| w.p3().clear_bit());
+// Only wakes main once the debouncer has an event for it, not on every tick.
+fn wdt_tick(cs: mspint::CriticalSection) -> bool {
+    // SAFETY: As in `main`.
+    unsafe {
+        BUTTON.with(cs, |b| {
+            b.tick();
+            b.has_events()
+        })
+    }
 }
 
-#[interrupt]
-fn TIMER0_A1(cs: CriticalSection) {
-    // let p = PERIPHERALS.borrow(cs).get().unwrap();
-    let mut t_ref = TIMER.borrow(cs).borrow_mut();
+wake_interrupt!(TIMER0_A1, timer_tick);
 
-    let t = t_ref.as_mut().unwrap();
-    t.timer_int();
+fn timer_tick(cs: mspint::CriticalSection) -> bool {
+    // let p = PERIPHERALS.borrow(cs).get().unwrap();
+    // SAFETY: As in `main`.
+    unsafe { TIMER.with(cs, |t| t.timer_int()) };
 
     // let port_1_2 = &p.PORT_1_2;
     // port_1_2.p1out.modify(|r, w| w.p0().bit(!r.p0().bit()));
+
+    true
 }
 
 #[no_mangle]
@@ -0,0 +1,102 @@
+//! Sharing data between a main thread and an interrupt handler with the `app!` macro from the
+//! [temp-hal] example's HAL.
+//!
+//! Instead of a static per shared value as in [timer], [timer-unsafe] and [timer-oncecell], the
+//! peripherals and state used by the interrupt handler are declared once as resources, which
+//! `init` returns. The handler gets them without locking or `unwrap()`, because msp430
+//! interrupt handlers don't nest. The idle loop can be interrupted, so it locks them.
+//!
+//! As with the other timer examples, this example uses the `TIMER0_A1` interrupt to blink LEDs
+//! on the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2) development kit: the red LED
+//! from the handler, and the green LED at a quarter of the rate from idle, which sleeps in
+//! between.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::app::Idle;
+use hal::lpm::LowPowerMode;
+
+extern crate panic_msp430;
+
+use msp430::interrupt as mspint;
+use {{device}}::{Peripherals, PORT_1_2, TIMER0_A3};
+
+app! {
+    resources: {
+        port_1_2: PORT_1_2,
+        timer: TIMER0_A3,
+        ticks: u16,
+    },
+    init: init,
+    idle: idle,
+    tasks: {
+        TIMER0_A1: blink,
+    },
+}
+
+fn init(_cs: mspint::CriticalSection) -> Resources {
+    let p = Peripherals::take().unwrap();
+
+    let wdt = &p.WATCHDOG_TIMER;
+    wdt.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let port_1_2 = p.PORT_1_2;
+    port_1_2
+        .p1dir
+        .modify(|_, w| w.p0().set_bit().p6().set_bit());
+    port_1_2
+        .p1out
+        .modify(|_, w| w.p0().set_bit().p6().clear_bit());
+
+    let clock = &p.SYSTEM_CLOCK;
+    clock.bcsctl3.modify(|_, w| w.lfxt1s().lfxt1s_2());
+    clock.bcsctl1.modify(|_, w| w.diva().diva_1());
+
+    let timer = p.TIMER0_A3;
+    timer.taccr0.write(|w| w.bits(1200));
+    timer.tactl.modify(|_, w| w.tassel().tassel_1().mc().mc_1());
+    timer.tacctl1.modify(|_, w| w.ccie().set_bit());
+    timer.taccr1.write(|w| w.bits(600));
+
+    Resources {
+        port_1_2,
+        timer,
+        ticks: 0,
+    }
+}
+
+fn idle(mut idle: Idle<Resources>) -> ! {
+    let mut seen = 0;
+
+    loop {
+        // The timer runs off ACLK, which keeps running in LPM3.
+        idle.sleep_unless(LowPowerMode::Lpm3, |res| res.ticks != seen);
+
+        idle.lock(|res, _cs| {
+            if res.ticks / 4 != seen / 4 {
+                res.port_1_2.p1out.modify(|r, w| w.p6().bit(!r.p6().bit()));
+            }
+
+            seen = res.ticks;
+        });
+    }
+}
+
+fn blink(res: &mut Resources, _cs: mspint::CriticalSection) {
+    res.timer.tacctl1.modify(|_, w| w.ccifg().clear_bit());
+    res.port_1_2.p1out.modify(|r, w| w.p0().bit(!r.p0().bit()));
+    res.ticks = res.ticks.wrapping_add(1);
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
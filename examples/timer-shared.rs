@@ -0,0 +1,99 @@
+//! Sharing data between a main thread and an interrupt handler safely, without run time checks.
+//!
+//! This is the [timer] example, with the [RefCell](core::cell::RefCell) and
+//! [Option](core::option::Option) around the shared peripherals replaced by the `Shared` cell
+//! from the [temp-hal] example's HAL. The peripherals are stored in the `pre_interrupt` hook,
+//! before any interrupt handler can run, so the handler gets them without checking whether they
+//! are there or already borrowed, and without the code to panic if they aren't. Built with
+//! `--release` for the msp430g2553 (rustc 1.101.0-nightly, 2026-10-17):
+//!
+//! | Example        | `.text` | `.data` | `.bss` | `TIMER0_A1` |
+//! |----------------|--------:|--------:|-------:|------------:|
+//! | [timer]        |     282 |       0 |      4 |          54 |
+//! | `timer-shared` |     220 |       0 |      1 |          14 |
+//!
+//! Sizes are in bytes, taken from the object fat LTO produces for each example, before linking.
+//! So they leave out libgcc and the vector table, which are the same for both.
+//!
+//! As with [timer], [timer-unsafe] and [timer-oncecell], this example uses the `TIMER0_A1`
+//! interrupt to blink LEDs on the [MSP-EXP430G2](http://www.ti.com/tool/MSP-EXP430G2)
+//! development kit.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(abi_msp430_interrupt)]
+#![feature(asm_experimental_arch)]
+
+#[path = "temp-hal/hal/mod.rs"]
+#[allow(unused)]
+mod hal;
+use hal::shared::Shared;
+
+extern crate panic_msp430;
+
+use msp430::{interrupt as mspint, critical_section as mspcs};
+use msp430_rt::entry;
+use {{device}}::{interrupt, Peripherals};
+
+static PERIPHERALS: Shared<Peripherals> = Shared::uninit();
+
+fn init(cs: mspint::CriticalSection) {
+    let p = Peripherals::take().unwrap();
+
+    let wdt = &p.WATCHDOG_TIMER;
+    wdt.wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    let port_1_2 = &p.PORT_1_2;
+    port_1_2
+        .p1dir
+        .modify(|_, w| w.p0().set_bit().p6().set_bit());
+    port_1_2
+        .p1out
+        .modify(|_, w| w.p0().set_bit().p6().clear_bit());
+
+    let clock = &p.SYSTEM_CLOCK;
+    clock.bcsctl3.modify(|_, w| w.lfxt1s().lfxt1s_2());
+    clock.bcsctl1.modify(|_, w| w.diva().diva_1());
+
+    let timer = &p.TIMER0_A3;
+    timer.taccr0.write(|w| w.bits(1200));
+    timer.tactl.modify(|_, w| w.tassel().tassel_1().mc().mc_1());
+    timer.tacctl1.modify(|_, w| w.ccie().set_bit());
+    timer.taccr1.write(|w| w.bits(600));
+
+    // SAFETY: Interrupts are still disabled, and this is the only call.
+    unsafe { PERIPHERALS.init(cs, p) };
+}
+
+#[entry(interrupt_enable(pre_interrupt = init))]
+fn main() -> ! {
+    loop {
+        mspcs::with(|_cs| {
+            // Do something while interrupts are disabled.
+        })
+    }
+}
+
+#[interrupt]
+fn TIMER0_A1(cs: CriticalSection) {
+    // SAFETY: Initialized in `init`, before interrupts were enabled, and not nested.
+    unsafe {
+        PERIPHERALS.with(cs, |p| {
+            let timer = &p.TIMER0_A3;
+            timer.tacctl1.modify(|_, w| w.ccifg().clear_bit());
+
+            let port_1_2 = &p.PORT_1_2;
+            port_1_2
+                .p1out
+                .modify(|r, w| w.p0().bit(!r.p0().bit()).p6().bit(!r.p6().bit()));
+        })
+    };
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}
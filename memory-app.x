@@ -0,0 +1,51 @@
+MEMORY
+{
+  /* Layout for an application started by the serial updater in updater/.
+     To build an application for the updater, use this file in place of
+     memory.x. The values are correct for the msp430g2553 and must match
+     updater/src/main.rs:
+
+     0xC000 - 0xC00F: Image header, written by the updater.
+     0xC010 - 0xDBDF: Application code and data (ROM).
+     0xDBE0 - 0xDBFF: Application vectors. The updater owns the hardware
+                      vectors at 0xFFE0 and forwards each one here.
+     0xDC00 - 0xF7FF: Staging area for images being received.
+     0xF800 - 0xFFFF: Updater, including the hardware vectors. */
+  RAM : ORIGIN = 0x0200, LENGTH = 0x0200
+  ROM : ORIGIN = 0xC010, LENGTH = 0x1BD0
+  VECTORS : ORIGIN = 0xDBE0, LENGTH = 0x20
+}
+
+/* Stack begins at the end of RAM:
+   _stack_start = ORIGIN(RAM) + LENGTH(RAM); */
+
+/* Statics placed in .noinit aren't zeroed or initialized at reset, so they
+   keep their values across resets that don't lose power (watchdog, RST pin,
+   etc.). After power-on they hold garbage. Placed after .data, so that
+   _enoinit is the end of all statics, and the stack has everything above it.
+   msp430-rt's _sheap (= _edata) doesn't account for .noinit. */
+SECTIONS
+{
+  .noinit (NOLOAD) : ALIGN(2)
+  {
+    _snoinit = .;
+    *(.noinit .noinit.*);
+    . = ALIGN(2);
+    _enoinit = .;
+  } > RAM
+} INSERT AFTER .data;
+
+/* Constant data above the 64kB mark, placed with the `far_data!` macro of the
+   examples' HAL. Parts with memory up there define a FAR_ROM region for it
+   (see memory-f5529.x); on parts without, far data simply goes to ROM. Code
+   can't be placed above 64kB: Rust only generates MSP430 code, whose CALL and
+   RET only handle 16-bit addresses. */
+REGION_ALIAS("FAR_ROM", ROM);
+
+SECTIONS
+{
+  .far_rodata : ALIGN(2)
+  {
+    *(.far_rodata .far_rodata.*);
+  } > FAR_ROM
+} INSERT AFTER .rodata;
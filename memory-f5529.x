@@ -0,0 +1,39 @@
+MEMORY
+{
+  /* Layout for the msp430f5529, as an example for parts with memory above
+     the 64kB mark. Copy over memory.x to use. The first 64kB are laid out as
+     on any other part; the remaining 81kB of flash become FAR_ROM. */
+  RAM : ORIGIN = 0x2400, LENGTH = 0x2000
+  ROM : ORIGIN = 0x4400, LENGTH = 0xBB80
+  VECTORS : ORIGIN = 0xFF80, LENGTH = 0x80
+  FAR_ROM : ORIGIN = 0x10000, LENGTH = 0x14400
+}
+
+/* Stack begins at the end of RAM:
+   _stack_start = ORIGIN(RAM) + LENGTH(RAM); */
+
+/* As in memory.x. */
+SECTIONS
+{
+  .noinit (NOLOAD) : ALIGN(2)
+  {
+    _snoinit = .;
+    *(.noinit .noinit.*);
+    . = ALIGN(2);
+    _enoinit = .;
+  } > RAM
+} INSERT AFTER .data;
+
+/* Constant data placed with `far_data!`. Only the data itself lives up here;
+   its 20-bit address is kept in ROM, where 16-bit code can reach it. Rust code
+   must never take a reference to far data directly, which the linker would
+   reject with a relocation overflow. Functions can't be placed here at all:
+   calling them would take the MSP430X CALLA and RETA instructions, which
+   Rust's msp430 target doesn't generate. */
+SECTIONS
+{
+  .far_rodata : ALIGN(2)
+  {
+    *(.far_rodata .far_rodata.*);
+  } > FAR_ROM
+} INSERT AFTER .rodata;
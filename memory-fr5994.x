@@ -0,0 +1,58 @@
+MEMORY
+{
+  /* Layout for the msp430fr5994, as an example for FRAM parts. Copy over
+     memory.x to use. Code, constants and persistent variables all live in
+     FRAM; the MPU keeps them apart (see `hal::fram::mpu_init` of the
+     examples' HAL). Its segment borders have a 1kB granularity, so
+     PERSISTENT and ROM must start on 1kB boundaries.
+
+     Untested: no example targets an FRAM part, so this layout, `persistent!`
+     and `mpu_init` have never been linked or run on an FR5994. The Rust side
+     has only been type-checked as part of the msp430g2553 examples. */
+  RAM : ORIGIN = 0x1C00, LENGTH = 0x1000
+  PERSISTENT : ORIGIN = 0x4000, LENGTH = 0x0400
+  ROM : ORIGIN = 0x4400, LENGTH = 0xBB80
+  VECTORS : ORIGIN = 0xFF80, LENGTH = 0x80
+  FAR_ROM : ORIGIN = 0x10000, LENGTH = 0x34000
+}
+
+/* Stack begins at the end of RAM:
+   _stack_start = ORIGIN(RAM) + LENGTH(RAM); */
+
+/* MPU segments: 1 is PERSISTENT (read/write), 2 is ROM and VECTORS
+   (read/execute), 3 is FAR_ROM (read only). The borders are given as
+   address bits 19:4, as programmed into the MPU, so that they fit 16 bits. */
+_mpu_border1 = ORIGIN(ROM) >> 4;
+_mpu_border2 = ORIGIN(FAR_ROM) >> 4;
+
+/* As in memory.x. */
+SECTIONS
+{
+  .noinit (NOLOAD) : ALIGN(2)
+  {
+    _snoinit = .;
+    *(.noinit .noinit.*);
+    . = ALIGN(2);
+    _enoinit = .;
+  } > RAM
+} INSERT AFTER .data;
+
+/* Variables placed with `persistent!`. Unlike .data, they are initialized
+   when the part is programmed rather than at every reset, and keep their
+   values across power loss. */
+SECTIONS
+{
+  .persistent : ALIGN(2)
+  {
+    *(.persistent .persistent.*);
+  } > PERSISTENT
+} INSERT AFTER .bss;
+
+/* As in memory-f5529.x. */
+SECTIONS
+{
+  .far_rodata : ALIGN(2)
+  {
+    *(.far_rodata .far_rodata.*);
+  } > FAR_ROM
+} INSERT AFTER .rodata;
@@ -12,5 +12,33 @@ MEMORY
 /* Stack begins at the end of RAM:
    _stack_start = ORIGIN(RAM) + LENGTH(RAM); */
 
-/* TODO: Code (and data?) above 64kB mark, which is supported even without
-   using MSP430X mode. */
+/* Statics placed in .noinit aren't zeroed or initialized at reset, so they
+   keep their values across resets that don't lose power (watchdog, RST pin,
+   etc.). After power-on they hold garbage. Placed after .data, so that
+   _enoinit is the end of all statics, and the stack has everything above it.
+   msp430-rt's _sheap (= _edata) doesn't account for .noinit. */
+SECTIONS
+{
+  .noinit (NOLOAD) : ALIGN(2)
+  {
+    _snoinit = .;
+    *(.noinit .noinit.*);
+    . = ALIGN(2);
+    _enoinit = .;
+  } > RAM
+} INSERT AFTER .data;
+
+/* Constant data above the 64kB mark, placed with the `far_data!` macro of the
+   examples' HAL. Parts with memory up there define a FAR_ROM region for it
+   (see memory-f5529.x); on parts without, far data simply goes to ROM. Code
+   can't be placed above 64kB: Rust only generates MSP430 code, whose CALL and
+   RET only handle 16-bit addresses. */
+REGION_ALIAS("FAR_ROM", ROM);
+
+SECTIONS
+{
+  .far_rodata : ALIGN(2)
+  {
+    *(.far_rodata .far_rodata.*);
+  } > FAR_ROM
+} INSERT AFTER .rodata;
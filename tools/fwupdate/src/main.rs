@@ -0,0 +1,211 @@
+//! Sends an application image to the serial updater in `updater/`.
+//!
+//! ```console
+//! $ cd tools/fwupdate
+//! $ cargo run --target <host triple> -- /dev/ttyACM0 ../../target/msp430-none-elf/release/app
+//! ```
+//!
+//! `--target` overrides the MSP430 target set by the template's `.cargo/config`. The
+//! image is either an ELF file, or Intel hex if its name ends in `.hex`. It must be linked with
+//! `memory-app.x`; anything outside the application area is rejected. Hold the button on P1.3
+//! while resetting the board to enter the updater if an application is already running.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use std::{env, fs, process};
+
+use object::elf::PT_LOAD;
+use object::read::elf::{ElfFile32, ProgramHeader};
+use object::Endianness;
+use serialport::{ClearBuffer, SerialPort};
+
+const SOF: u8 = 0x7E;
+const ACK: u8 = 0x06;
+const CMD_HELLO: u8 = 0x01;
+const CMD_ERASE: u8 = 0x02;
+const CMD_WRITE: u8 = 0x03;
+const CMD_COMMIT: u8 = 0x04;
+const CHUNK: usize = 64;
+const RETRIES: usize = 3;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <serial port> <image.elf|image.hex>", args[0]);
+        process::exit(2);
+    }
+
+    if let Err(e) = run(&args[1], Path::new(&args[2])) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(port: &str, path: &Path) -> Result<()> {
+    let mut port = serialport::new(port, 9600)
+        .timeout(Duration::from_secs(1))
+        .open()?;
+    port.clear(ClearBuffer::All)?;
+
+    let (start, end) = hello(&mut *port)?;
+    let image = load(path, start, end)?;
+    let crc = crc16(&image);
+    println!(
+        "image: {} bytes at {:#06x}, crc {:#06x}",
+        image.len(),
+        start,
+        crc
+    );
+
+    command(&mut *port, CMD_ERASE, &[], Duration::from_secs(2))?;
+
+    for (i, chunk) in image.chunks(CHUNK).enumerate() {
+        // The staging area was just erased.
+        if chunk.iter().all(|&b| b == 0xFF) {
+            continue;
+        }
+
+        let addr = start + (i * CHUNK) as u16;
+        let mut payload = addr.to_le_bytes().to_vec();
+        payload.extend_from_slice(chunk);
+        command(&mut *port, CMD_WRITE, &payload, Duration::from_secs(1))?;
+
+        print!("\rwritten {}/{} bytes", i * CHUNK + chunk.len(), image.len());
+        std::io::stdout().flush()?;
+    }
+    println!();
+
+    let mut payload = (image.len() as u16).to_le_bytes().to_vec();
+    payload.extend_from_slice(&crc.to_le_bytes());
+    // Checks the image, then the updater copies it into place and restarts.
+    command(&mut *port, CMD_COMMIT, &payload, Duration::from_secs(5))?;
+    println!("done");
+
+    Ok(())
+}
+
+// Returns the application area, [start, end).
+fn hello(port: &mut dyn SerialPort) -> Result<(u16, u16)> {
+    command(port, CMD_HELLO, &[], Duration::from_secs(1))
+        .map_err(|e| format!("no response from updater ({}); is the board in the updater?", e))?;
+
+    let mut area = [0; 4];
+    port.read_exact(&mut area)?;
+    Ok((
+        u16::from_le_bytes([area[0], area[1]]),
+        u16::from_le_bytes([area[2], area[3]]),
+    ))
+}
+
+// Send a frame and wait for its ACK, retrying on NAK or timeout.
+fn command(port: &mut dyn SerialPort, cmd: u8, payload: &[u8], timeout: Duration) -> Result<()> {
+    let mut frame = vec![SOF, cmd, payload.len() as u8];
+    frame.extend_from_slice(payload);
+    let crc = crc16(&frame[1..]);
+    frame.extend_from_slice(&crc.to_le_bytes());
+
+    port.set_timeout(timeout)?;
+
+    let mut last_err: Box<dyn Error> = "no attempts".into();
+    for _ in 0..RETRIES {
+        port.write_all(&frame)?;
+
+        let mut reply = [0];
+        match port.read_exact(&mut reply) {
+            Ok(()) if reply[0] == ACK => return Ok(()),
+            Ok(()) => last_err = format!("command {:#04x} rejected", cmd).into(),
+            Err(e) => last_err = e.into(),
+        }
+
+        // Drop whatever is left of a garbled exchange before trying again.
+        std::thread::sleep(Duration::from_millis(100));
+        port.clear(ClearBuffer::Input)?;
+    }
+
+    Err(last_err)
+}
+
+// The image covers [start, highest loaded address), with gaps filled with 0xFF. Its length is
+// kept even, since the updater writes words.
+fn load(path: &Path, start: u16, end: u16) -> Result<Vec<u8>> {
+    let data = fs::read(path)?;
+    let mut image = vec![0xFF; usize::from(end - start)];
+    let mut top = 0;
+
+    let mut place = |addr: u32, bytes: &[u8]| -> Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let last = addr + bytes.len() as u32;
+        if addr < u32::from(start) || last > u32::from(end) {
+            return Err(format!(
+                "data at {:#06x}..{:#06x} is outside the application area; \
+                 was the image linked with memory-app.x?",
+                addr, last
+            )
+            .into());
+        }
+
+        let offset = (addr - u32::from(start)) as usize;
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        top = top.max(offset + bytes.len());
+        Ok(())
+    };
+
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("hex")) {
+        let mut base = 0u32;
+        for record in ihex::Reader::new(std::str::from_utf8(&data)?) {
+            match record? {
+                ihex::Record::Data { offset, value } => place(base + u32::from(offset), &value)?,
+                ihex::Record::ExtendedSegmentAddress(seg) => base = u32::from(seg) << 4,
+                ihex::Record::ExtendedLinearAddress(hi) => base = u32::from(hi) << 16,
+                _ => {}
+            }
+        }
+    } else {
+        let elf = ElfFile32::<Endianness>::parse(&*data)?;
+        let endian = elf.endian();
+
+        // Load addresses, so initial values of .data are placed in flash.
+        for ph in elf.elf_program_headers() {
+            if ph.p_type(endian) != PT_LOAD {
+                continue;
+            }
+
+            let bytes = ph
+                .data(endian, &*data)
+                .map_err(|()| "truncated ELF segment")?;
+            place(ph.p_paddr(endian), bytes)?;
+        }
+    }
+
+    if top == 0 {
+        return Err("image is empty".into());
+    }
+
+    image.truncate((top + 1) & !1);
+    Ok(image)
+}
+
+// CRC-16/CCITT-FALSE, as computed by the updater.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+
+    for &b in bytes {
+        crc ^= u16::from(b) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
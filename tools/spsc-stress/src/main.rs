@@ -0,0 +1,165 @@
+//! Stress test for the SPSC queue in the temp-hal example's HAL, on the host.
+//!
+//! ```console
+//! $ cd tools/spsc-stress
+//! $ cargo run --release --target <host triple> -- [rounds]
+//! ```
+//!
+//! `--target` overrides the MSP430 target set by the template's `.cargo/config`. A producer and
+//! a consumer thread stand in for an interrupt handler and main. Each round passes enough values
+//! through queues of several capacities for the 16-bit indices to wrap a few times, with random
+//! pauses on both sides to vary the interleavings. The consumer checks that values arrive in
+//! order, exactly once, and that every value pushed is eventually dropped exactly once.
+//!
+//! On the host, the queue's indices are `AtomicU16`s rather than the volatile accesses used on
+//! the MSP430, so this tests the algorithm, not the MSP430 build.
+
+#[path = "../../../examples/temp-hal/hal/spsc.rs"]
+#[allow(dead_code)]
+mod spsc;
+
+use std::process;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::thread;
+
+use spsc::Queue;
+
+// More than 2^16, so the indices wrap.
+const VALUES: u32 = 200_000;
+
+// Values pushed but not yet dropped.
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+// Counts live instances, to catch values that are read twice or never dropped.
+struct Tracked(u32);
+
+impl Tracked {
+    fn new(value: u32) -> Self {
+        LIVE.fetch_add(1, Ordering::Relaxed);
+        Tracked(value)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        LIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Xorshift, to pause at random points without pulling in a crate.
+struct Jitter(u32);
+
+impl Jitter {
+    fn pause(&mut self) {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        match self.0 % 64 {
+            0 => thread::yield_now(),
+            1..=3 => {
+                for _ in 0..(self.0 >> 8) % 256 {
+                    std::hint::spin_loop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Exits right away, since the producer may be stuck on a full queue.
+fn fail(msg: String) -> ! {
+    eprintln!("round failed: {}", msg);
+    process::exit(1);
+}
+
+fn round<const N: usize>(seed: u32) {
+    let mut queue = Queue::<Tracked, N>::new();
+    let (mut tx, mut rx) = queue.split();
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            let mut jitter = Jitter(seed | 1);
+
+            for i in 0..VALUES {
+                let mut value = Tracked::new(i);
+                loop {
+                    assert!(tx.len() <= N);
+                    match tx.push(value) {
+                        Ok(()) => break,
+                        Err(v) => value = v,
+                    }
+                    jitter.pause();
+                }
+                jitter.pause();
+            }
+        });
+
+        let mut jitter = Jitter(seed.rotate_left(16) | 1);
+        let mut expected = 0;
+
+        while expected < VALUES {
+            if rx.len() > N {
+                fail(format!("capacity {}: length {}", N, rx.len()));
+            }
+
+            if let Some(value) = rx.pop() {
+                if value.0 != expected {
+                    fail(format!("capacity {}: got {}, expected {}", N, value.0, expected));
+                }
+                expected += 1;
+            }
+            jitter.pause();
+        }
+    });
+
+    if rx.pop().is_some() {
+        fail(format!("capacity {}: value after the last one", N));
+    }
+}
+
+fn check_dropped() {
+    match LIVE.load(Ordering::Relaxed) {
+        0 => {}
+        n => fail(format!("{} values never dropped", n)),
+    }
+}
+
+// Values left in the queue are dropped with it.
+fn drop_leftovers() {
+    {
+        let mut queue = Queue::<Tracked, 4>::new();
+        let (mut tx, mut rx) = queue.split();
+
+        for i in 0..6 {
+            let _ = tx.push(Tracked::new(i));
+        }
+        rx.pop();
+    }
+
+    check_dropped();
+}
+
+fn main() {
+    let rounds: u32 = match std::env::args().nth(1).map(|s| s.parse()) {
+        None => 10,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("usage: spsc-stress [rounds]");
+            process::exit(2);
+        }
+    };
+
+    drop_leftovers();
+
+    for r in 0..rounds {
+        let seed = r.wrapping_mul(0x9E37_79B9) ^ 0x5EED;
+        round::<1>(seed);
+        round::<2>(seed);
+        round::<8>(seed);
+        round::<64>(seed);
+        check_dropped();
+    }
+
+    println!("{} rounds ok", rounds);
+}
@@ -0,0 +1,11 @@
+MEMORY
+{
+  /* The updater lives in the last 2kB of flash of the msp430g2553. See
+     memory-app.x in the template root for the layout of the rest. */
+  RAM : ORIGIN = 0x0200, LENGTH = 0x0200
+  ROM : ORIGIN = 0xF800, LENGTH = 0x07E0
+  VECTORS : ORIGIN = 0xFFE0, LENGTH = 0x20
+}
+
+/* Stack begins at the end of RAM:
+   _stack_start = ORIGIN(RAM) + LENGTH(RAM); */
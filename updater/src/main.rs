@@ -0,0 +1,373 @@
+//! Resident serial updater for the msp430g2553.
+//!
+//! Lives in the last 2kB of flash, and owns the hardware reset and interrupt vectors. At reset,
+//! it starts the application (built with `memory-app.x` from the template root) unless:
+//!
+//! - the button on P1.3 is held down,
+//! - the application's image header is missing or its CRC doesn't match, e.g. after an
+//!   interrupted update,
+//!
+//! in which case it waits for `tools/fwupdate` to send an image over the USCI_A0 UART (9600
+//! baud, P1.1/P1.2, the HW UART of the MSP-EXP430G2).
+//!
+//! Images are received into a staging area the size of the application area, and only copied
+//! over the application once complete and CRC-checked. A power loss during the copy leaves the
+//! staged image in place, and the copy is redone at the next reset.
+//!
+//! Interrupts are forwarded to the application's vector table at 0xDBE0 by a `br` through the
+//! matching entry; the updater itself runs with interrupts disabled.
+//!
+//! Build with `cargo build --release` from this directory; the template's `.cargo/config` is
+//! picked up from the parent directory.
+//!
+//! ---
+
+#![no_main]
+#![no_std]
+#![feature(asm_experimental_arch)]
+
+extern crate panic_msp430;
+
+use core::arch::global_asm;
+use core::ptr;
+
+use msp430::asm;
+use msp430_rt::entry;
+use {{device}}::Peripherals;
+
+// Must match memory-app.x.
+const APP_HEADER: u16 = 0xC000;
+const APP_START: u16 = 0xC010;
+const APP_END: u16 = 0xDC00;
+// The staging area mirrors the application area, header included.
+const STAGING_OFFSET: u16 = 0x1C00;
+const SEGMENT_SIZE: u16 = 512;
+
+const IMAGE_MAGIC: u16 = 0xB007;
+
+const SOF: u8 = 0x7E;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CMD_HELLO: u8 = 0x01;
+const CMD_ERASE: u8 = 0x02;
+const CMD_WRITE: u8 = 0x03;
+const CMD_COMMIT: u8 = 0x04;
+// Address plus 64 bytes of data.
+const MAX_PAYLOAD: usize = 66;
+
+const FWKEY: u16 = 0xA500;
+const FCTL1_ERASE: u16 = 1 << 1;
+const FCTL1_WRT: u16 = 1 << 6;
+const FCTL2_SMCLK_DIV3: u16 = (0b10 << 6) | 2;
+const FCTL3_BUSY: u16 = 1 << 0;
+const FCTL3_LOCK: u16 = 1 << 4;
+
+const CALDCO_1MHZ: *const u8 = 0x10FE as *const u8;
+const CALBC1_1MHZ: *const u8 = 0x10FF as *const u8;
+
+// Forward every interrupt to the application's vector table; `mov &addr, pc` (`br &addr`) jumps
+// to the address stored at `addr`, without touching any other registers.
+global_asm!(
+    ".macro forward name, entry
+        .section .text.\\name, \"ax\", @progbits
+        .global \\name
+        .type \\name, @function
+    \\name:
+        mov &\\entry, r0
+    .endm",
+    "forward PORT1, 0xDBE4",
+    "forward PORT2, 0xDBE6",
+    "forward ADC10, 0xDBEA",
+    "forward USCIAB0TX, 0xDBEC",
+    "forward USCIAB0RX, 0xDBEE",
+    "forward TIMER0_A1, 0xDBF0",
+    "forward TIMER0_A0, 0xDBF2",
+    "forward WDT, 0xDBF4",
+    "forward COMPARATORA, 0xDBF6",
+    "forward TIMER1_A1, 0xDBF8",
+    "forward TIMER1_A0, 0xDBFA",
+    "forward NMI, 0xDBFC",
+);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Header {
+    magic: u16,
+    len: u16,
+    crc: u16,
+}
+
+impl Header {
+    fn read(addr: u16) -> Self {
+        Header {
+            magic: read_word(addr),
+            len: read_word(addr + 2),
+            crc: read_word(addr + 4),
+        }
+    }
+
+    // The image described by the header at `header` is complete and intact.
+    fn is_valid_at(header: u16) -> bool {
+        let h = Header::read(header);
+        let start = header + (APP_START - APP_HEADER);
+
+        h.magic == IMAGE_MAGIC
+            && h.len <= APP_END - APP_START
+            && h.len % 2 == 0
+            && crc16(start, h.len) == h.crc
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+
+    p.WATCHDOG_TIMER
+        .wdtctl
+        .write(|w| w.wdtpw().password().wdthold().set_bit());
+
+    // SAFETY: Info memory is always mapped and readable.
+    let (bc1, dco) =
+        unsafe { (ptr::read_volatile(CALBC1_1MHZ), ptr::read_volatile(CALDCO_1MHZ)) };
+    if bc1 != 0xFF || dco != 0xFF {
+        p.SYSTEM_CLOCK.bcsctl1.write(|w| unsafe { w.bits(bc1) });
+        p.SYSTEM_CLOCK.dcoctl.write(|w| w.bits(dco));
+    }
+
+    p.FLASH
+        .fctl2
+        .write(|w| unsafe { w.bits(FWKEY | FCTL2_SMCLK_DIV3) });
+
+    let port = &p.PORT_1_2;
+    port.p1ren.modify(|_, w| w.p3().set_bit());
+    port.p1out.modify(|_, w| w.p3().set_bit());
+    delay(100); // Let the pull-up charge the pin.
+    let requested = port.p1in.read().p3().bit_is_clear();
+    port.p1ren.modify(|_, w| w.p3().clear_bit());
+    port.p1out.modify(|_, w| w.p3().clear_bit());
+
+    let staging_header = APP_HEADER + STAGING_OFFSET;
+    if Header::is_valid_at(staging_header)
+        && Header::read(staging_header) != Header::read(APP_HEADER)
+    {
+        install(&p.FLASH);
+    }
+
+    if !requested && Header::is_valid_at(APP_HEADER) {
+        start_app();
+    }
+
+    // UCA0TXD/UCA0RXD on P1.2/P1.1, 9600 baud from the 1 MHz SMCLK.
+    port.p1sel.modify(|_, w| w.p1().set_bit().p2().set_bit());
+    port.p1sel2.modify(|_, w| w.p1().set_bit().p2().set_bit());
+    let uart = &p.USCI_A0_UART_MODE;
+    uart.uca0ctl1.modify(|_, w| w.ucswrst().set_bit());
+    uart.uca0ctl1.modify(|_, w| w.ucssel().ucssel_2());
+    uart.uca0br0.write(|w| w.bits(104));
+    uart.uca0br1.write(|w| w.bits(0));
+    uart.uca0mctl.modify(|_, w| w.ucbrs().bits(1));
+    uart.uca0ctl1.modify(|_, w| w.ucswrst().clear_bit());
+
+    let mut payload = [0u8; MAX_PAYLOAD];
+
+    loop {
+        while rx(&p) != SOF {}
+
+        let cmd = rx(&p);
+        let len = usize::from(rx(&p));
+        if len > MAX_PAYLOAD {
+            tx(&p, NAK);
+            continue;
+        }
+
+        for b in payload[..len].iter_mut() {
+            *b = rx(&p);
+        }
+        let crc = u16::from_le_bytes([rx(&p), rx(&p)]);
+
+        let mut expected = crc16_update(0xFFFF, cmd);
+        expected = crc16_update(expected, len as u8);
+        for &b in payload[..len].iter() {
+            expected = crc16_update(expected, b);
+        }
+        if crc != expected {
+            tx(&p, NAK);
+            continue;
+        }
+
+        let ok = match cmd {
+            CMD_HELLO => {
+                tx(&p, ACK);
+                for b in APP_START.to_le_bytes().iter().chain(APP_END.to_le_bytes().iter()) {
+                    tx(&p, *b);
+                }
+                continue;
+            }
+            CMD_ERASE => {
+                for seg in (APP_HEADER..APP_END).step_by(SEGMENT_SIZE as usize) {
+                    erase_segment(&p.FLASH, seg + STAGING_OFFSET);
+                }
+                true
+            }
+            CMD_WRITE => write_staging(&p.FLASH, &payload[..len]),
+            CMD_COMMIT if len == 4 => {
+                let len = u16::from_le_bytes([payload[0], payload[1]]);
+                let crc = u16::from_le_bytes([payload[2], payload[3]]);
+                commit(&p.FLASH, len, crc)
+            }
+            _ => false,
+        };
+
+        tx(&p, if ok { ACK } else { NAK });
+
+        if ok && cmd == CMD_COMMIT {
+            while uart.uca0stat.read().ucbusy().bit_is_set() {}
+            reset();
+        }
+    }
+}
+
+// Payload: little-endian application address, followed by an even number of data bytes.
+fn write_staging(flash: &{{device}}::FLASH, payload: &[u8]) -> bool {
+    if payload.len() < 2 || payload.len() % 2 != 0 {
+        return false;
+    }
+
+    let addr = u16::from_le_bytes([payload[0], payload[1]]);
+    let data = &payload[2..];
+    if !(APP_START..APP_END).contains(&addr)
+        || addr % 2 != 0
+        || usize::from(APP_END - addr) < data.len()
+    {
+        return false;
+    }
+
+    for (i, pair) in data.chunks(2).enumerate() {
+        let dst = addr + STAGING_OFFSET + i as u16 * 2;
+        let word = u16::from_le_bytes([pair[0], pair[1]]);
+        write_word(flash, dst, word);
+
+        // Catches writes over data that wasn't erased first.
+        if read_word(dst) != word {
+            return false;
+        }
+    }
+
+    true
+}
+
+// Check the staged image, and mark it complete by writing its header last. The copy is done
+// by `install`.
+fn commit(flash: &{{device}}::FLASH, len: u16, crc: u16) -> bool {
+    let header = APP_HEADER + STAGING_OFFSET;
+
+    if len > APP_END - APP_START || len % 2 != 0 || crc16(APP_START + STAGING_OFFSET, len) != crc
+    {
+        return false;
+    }
+
+    write_word(flash, header + 2, len);
+    write_word(flash, header + 4, crc);
+    write_word(flash, header, IMAGE_MAGIC);
+
+    Header::is_valid_at(header)
+}
+
+// Copy the staged image over the application, header last.
+fn install(flash: &{{device}}::FLASH) {
+    let staged = Header::read(APP_HEADER + STAGING_OFFSET);
+
+    for seg in (APP_HEADER..APP_END).step_by(SEGMENT_SIZE as usize) {
+        erase_segment(flash, seg);
+    }
+
+    for addr in (APP_START..APP_START + staged.len).step_by(2) {
+        let word = read_word(addr + STAGING_OFFSET);
+        if word != 0xFFFF {
+            write_word(flash, addr, word);
+        }
+    }
+
+    write_word(flash, APP_HEADER + 2, staged.len);
+    write_word(flash, APP_HEADER + 4, staged.crc);
+    write_word(flash, APP_HEADER, staged.magic);
+}
+
+fn start_app() -> ! {
+    // SAFETY: The application's header was checked, so its reset vector points to its reset
+    // handler, which sets up the stack and everything else from scratch.
+    unsafe { core::arch::asm!("mov &0xDBFE, r0", options(noreturn)) }
+}
+
+// A watchdog write without the password causes a PUC, which restarts the updater.
+fn reset() -> ! {
+    // SAFETY: Deliberately writing a bad password.
+    unsafe { ptr::write_volatile(0x0120 as *mut u16, 0) };
+    loop {
+        asm::nop();
+    }
+}
+
+fn rx(p: &Peripherals) -> u8 {
+    while p.SPECIAL_FUNCTION.ifg2.read().uca0rxifg().bit_is_clear() {}
+    p.USCI_A0_UART_MODE.uca0rxbuf.read().bits()
+}
+
+fn tx(p: &Peripherals, byte: u8) {
+    while p.SPECIAL_FUNCTION.ifg2.read().uca0txifg().bit_is_clear() {}
+    p.USCI_A0_UART_MODE.uca0txbuf.write(|w| w.bits(byte));
+}
+
+fn erase_segment(flash: &{{device}}::FLASH, addr: u16) {
+    flash_op(flash, FCTL1_ERASE, || unsafe {
+        ptr::write_volatile(addr as *mut u16, 0)
+    });
+}
+
+fn write_word(flash: &{{device}}::FLASH, addr: u16, word: u16) {
+    flash_op(flash, FCTL1_WRT, || unsafe {
+        ptr::write_volatile(addr as *mut u16, word)
+    });
+}
+
+// Interrupts are never enabled in the updater, so there's no need to disable them here.
+fn flash_op(flash: &{{device}}::FLASH, mode: u16, op: impl FnOnce()) {
+    flash.fctl3.write(|w| unsafe { w.bits(FWKEY) });
+    flash.fctl1.write(|w| unsafe { w.bits(FWKEY | mode) });
+    op();
+    while flash.fctl3.read().bits() & FCTL3_BUSY != 0 {}
+    flash.fctl1.write(|w| unsafe { w.bits(FWKEY) });
+    flash.fctl3.write(|w| unsafe { w.bits(FWKEY | FCTL3_LOCK) });
+}
+
+fn read_word(addr: u16) -> u16 {
+    // SAFETY: Flash is always mapped and readable.
+    unsafe { ptr::read_volatile(addr as *const u16) }
+}
+
+// CRC-16/CCITT-FALSE over `len` bytes of flash.
+fn crc16(start: u16, len: u16) -> u16 {
+    (start..start + len).fold(0xFFFF, |crc, addr| {
+        // SAFETY: As for `read_word`.
+        crc16_update(crc, unsafe { ptr::read_volatile(addr as *const u8) })
+    })
+}
+
+// Bytewise form of the bitwise algorithm, several times faster without a multiplier.
+fn crc16_update(crc: u16, byte: u8) -> u16 {
+    let mut crc = crc.rotate_left(8) ^ u16::from(byte);
+    crc ^= (crc & 0xFF) >> 4;
+    crc ^= crc << 12;
+    crc ^= (crc & 0xFF) << 5;
+    crc
+}
+
+fn delay(n: u16) {
+    for _ in 0..n {
+        asm::nop();
+    }
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!();
+}